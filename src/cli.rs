@@ -0,0 +1,283 @@
+use crate::mips::instruction::Isa;
+use crate::runtime::breakpoints::{Breakpoint, BreakpointLocation};
+use crate::runtime::expression::Expression;
+use crate::runtime::memory::parse_address;
+use crate::runtime::trace::TraceFormat;
+use crate::runtime::vm::VMOptions;
+use crate::runtime::watchpoints::Watchpoint;
+
+pub const USAGE: &str = "\
+Usage: juno [run | disasm] [options] [program.bin] [-- arguments...]
+       juno trace-diff <trace> <trace>
+
+Commands:
+    run                   Run the program without the terminal UI
+    disasm                Print the disassembly of the program
+    trace-diff            Report the first step at which two traces differ
+
+Options:
+    --isa <isa>           Restrict the instruction set to mips1, mips32 or mips32r2
+    --delayed-branching   Execute the instruction after a branch or jump before it is taken
+    --load-delay          Hide loaded values from the next instruction, and warn when it reads them
+    --symbols <file>      Read labels, source lines and the source file of the program from a
+                          symbol file, as described in README.md
+    --watch <range>[:rwc] Stop on reads (r), writes (w) or changes (c) of memory in the range,
+                          given as an address or low-high (default: w)
+    --break <location>[ if <condition>]
+                          Stop before an address or line:N, optionally only when the
+                          condition holds, e.g. \"0x400010 if $t0 == 5 && hits >= 10\"
+    --watch-expr <expr>   Show the value of an expression in the watch panel
+    --stats               After run, print instruction counts and a cycle estimate
+    --profile             After run, print the instructions executed by each function and line
+    --profile-folded <file>
+                          After run, write the profile as folded stacks for flamegraph tools
+    --coverage <file>     After run, write line and branch coverage in lcov format, which needs
+                          --symbols, and print a summary for each label
+    --trace <file>        Write each executed instruction and its effects to a trace
+    --trace-format <format>
+                          Write the trace as text (the default) or binary
+    -h, --help            Show this message
+
+Arguments after -- are passed to the program in argc ($a0) and argv ($a1).";
+
+/// How the program is run.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Run the program in the terminal UI.
+    Debug,
+    /// Run the program to completion without the terminal UI.
+    Run,
+    /// Print the disassembly of the program.
+    Disassemble,
+    /// Compare two traces.
+    TraceDiff,
+    Help,
+}
+
+pub struct Arguments {
+    pub command: Command,
+    /// The path of the raw binary to load, if any.
+    pub program: Option<String>,
+    pub options: VMOptions,
+    /// The symbol file giving the debug information of the program, if any.
+    pub symbols: Option<String>,
+    pub watchpoints: Vec<Watchpoint>,
+    pub breakpoints: Vec<Breakpoint>,
+    /// Expressions shown in the watch panel of the terminal UI.
+    pub watch_expressions: Vec<Expression>,
+    /// Arguments for the program itself, given after `--`.
+    pub program_arguments: Vec<String>,
+    /// Whether to print execution statistics after a headless run.
+    pub stats: bool,
+    /// Whether to print a profile after a headless run.
+    pub profile: bool,
+    /// The file to write the profile of a headless run to, as folded stacks.
+    pub profile_folded: Option<String>,
+    /// The file to write the lcov coverage of a headless run to.
+    pub coverage: Option<String>,
+    /// The file to write a trace of the execution to.
+    pub trace: Option<String>,
+    pub trace_format: TraceFormat,
+    /// The traces to compare with trace-diff.
+    pub trace_files: Vec<String>,
+}
+
+/// Parse the command line arguments, not including the name of the executable.
+pub fn parse_arguments(args: impl IntoIterator<Item = String>) -> Result<Arguments, String> {
+    let mut arguments = Arguments {
+        command: Command::Debug,
+        program: None,
+        options: VMOptions::default(),
+        symbols: None,
+        watchpoints: vec![],
+        breakpoints: vec![],
+        watch_expressions: vec![],
+        program_arguments: vec![],
+        stats: false,
+        profile: false,
+        profile_folded: None,
+        coverage: None,
+        trace: None,
+        trace_format: TraceFormat::Text,
+        trace_files: vec![],
+    };
+
+    let mut args = args.into_iter().peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("run") => arguments.command = Command::Run,
+        Some("disasm") => arguments.command = Command::Disassemble,
+        Some("trace-diff") => arguments.command = Command::TraceDiff,
+        _ => {}
+    }
+
+    if arguments.command != Command::Debug {
+        args.next();
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => {
+                arguments.program_arguments = args.by_ref().collect();
+            }
+            "-h" | "--help" => arguments.command = Command::Help,
+            "--isa" => {
+                let isa = args
+                    .next()
+                    .ok_or_else(|| String::from("--isa requires a value"))?;
+
+                arguments.options.isa = parse_isa(&isa)?;
+            }
+            "--delayed-branching" => arguments.options.delayed_branching = true,
+            "--load-delay" => arguments.options.load_delay = true,
+            "--symbols" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| String::from("--symbols requires a value"))?;
+
+                arguments.symbols = Some(path);
+            }
+            "--stats" => arguments.stats = true,
+            "--profile" => arguments.profile = true,
+            "--profile-folded" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| String::from("--profile-folded requires a value"))?;
+
+                arguments.profile_folded = Some(path);
+            }
+            "--coverage" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| String::from("--coverage requires a value"))?;
+
+                arguments.coverage = Some(path);
+            }
+            "--trace" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| String::from("--trace requires a value"))?;
+
+                arguments.trace = Some(path);
+            }
+            "--trace-format" => {
+                let format = args
+                    .next()
+                    .ok_or_else(|| String::from("--trace-format requires a value"))?;
+
+                arguments.trace_format = parse_trace_format(&format)?;
+            }
+            "--watch" => {
+                let watch = args
+                    .next()
+                    .ok_or_else(|| String::from("--watch requires a value"))?;
+
+                arguments.watchpoints.push(parse_watchpoint(&watch)?);
+            }
+            "--break" => {
+                let breakpoint = args
+                    .next()
+                    .ok_or_else(|| String::from("--break requires a value"))?;
+
+                arguments.breakpoints.push(parse_breakpoint(&breakpoint)?);
+            }
+            "--watch-expr" => {
+                let expression = args
+                    .next()
+                    .ok_or_else(|| String::from("--watch-expr requires a value"))?;
+
+                arguments
+                    .watch_expressions
+                    .push(Expression::parse(&expression)?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option \"{}\"", arg)),
+            _ if arguments.command == Command::TraceDiff => arguments.trace_files.push(arg),
+            _ if arguments.program.is_none() => arguments.program = Some(arg),
+            _ => return Err(format!("Unexpected argument \"{}\"", arg)),
+        }
+    }
+
+    match arguments.command {
+        Command::Run if arguments.program.is_none() => {
+            return Err(String::from("run requires a program"))
+        }
+        Command::Disassemble if arguments.program.is_none() => {
+            return Err(String::from("disasm requires a program"))
+        }
+        Command::TraceDiff if arguments.trace_files.len() != 2 => {
+            return Err(String::from("trace-diff requires two traces"))
+        }
+        _ => {}
+    }
+
+    Ok(arguments)
+}
+
+fn parse_trace_format(name: &str) -> Result<TraceFormat, String> {
+    match name.to_lowercase().as_str() {
+        "text" => Ok(TraceFormat::Text),
+        "binary" => Ok(TraceFormat::Binary),
+        _ => Err(format!("Unknown trace format \"{}\"", name)),
+    }
+}
+
+fn parse_isa(name: &str) -> Result<Isa, String> {
+    match name.to_lowercase().as_str() {
+        "mips1" | "mips-i" => Ok(Isa::MipsI),
+        "mips32" => Ok(Isa::Mips32),
+        "mips32r2" => Ok(Isa::Mips32R2),
+        _ => Err(format!("Unknown instruction set \"{}\"", name)),
+    }
+}
+
+/// Parse a watchpoint such as "0x10010000", "0x10010000-0x1001000f:rw" or "268500992:c".
+fn parse_watchpoint(text: &str) -> Result<Watchpoint, String> {
+    let (range, accesses) = text.split_once(':').unwrap_or((text, "w"));
+
+    let (low, high) = match range.split_once('-') {
+        Some((low, high)) => (parse_address(low)?, parse_address(high)?),
+        None => {
+            let address = parse_address(range)?;
+            (address, address)
+        }
+    };
+
+    if low > high {
+        return Err(format!("Invalid watchpoint range \"{}\"", range));
+    }
+
+    let mut watchpoint = Watchpoint::new(low, high);
+    watchpoint.on_write = false;
+
+    for access in accesses.chars() {
+        match access {
+            'r' => watchpoint.on_read = true,
+            'w' => watchpoint.on_write = true,
+            'c' => watchpoint.on_change = true,
+            _ => return Err(format!("Unknown watchpoint access \"{}\"", access)),
+        }
+    }
+
+    Ok(watchpoint)
+}
+
+/// Parse a breakpoint such as "0x400010", "line:12" or "0x400010 if $t0 == 5".
+fn parse_breakpoint(text: &str) -> Result<Breakpoint, String> {
+    let (location, condition) = match text.split_once(" if ") {
+        Some((location, condition)) => (location.trim(), Some(Expression::parse(condition)?)),
+        None => (text.trim(), None),
+    };
+
+    let location = match location.strip_prefix("line:") {
+        Some(line) => BreakpointLocation::Line(
+            line.parse()
+                .map_err(|_| format!("Invalid line number \"{}\"", line))?,
+        ),
+        None => BreakpointLocation::Address(parse_address(location)?),
+    };
+
+    Ok(match condition {
+        Some(condition) => Breakpoint::with_condition(location, condition),
+        None => Breakpoint::new(location),
+    })
+}
@@ -0,0 +1,104 @@
+use std::io::{self, BufRead, Write};
+
+use crate::runtime::logging::Logger;
+use crate::runtime::stepping::{Run, RunTarget, StopReason};
+use crate::runtime::vm::VM;
+
+/// The number of instructions executed between flushes of the program's output.
+const BATCH_SIZE: usize = 1000;
+
+/// Run the program to completion without the terminal UI, with its input and output on the
+/// standard streams. Warnings and errors are logged to standard error.
+///
+/// Return the exit code of the program, or 1 if it stopped with an error.
+pub fn run(vm: &mut VM) -> i32 {
+    run_with(vm, &mut io::stdin().lock(), &mut io::stdout())
+}
+
+/// Run the program to completion, reading its input from `input` and writing its output to
+/// `output`. Breakpoints are logged and then passed.
+fn run_with(vm: &mut VM, input: &mut impl BufRead, output: &mut impl Write) -> i32 {
+    let logger = Logger {};
+    let mut run = Run::new(RunTarget::Continue);
+
+    loop {
+        let result = vm.run(&mut run, BATCH_SIZE);
+
+        write!(output, "{}", vm.take_output()).ok();
+        output.flush().ok();
+
+        for warning in vm.take_warnings() {
+            logger.warning(&warning);
+        }
+
+        match result {
+            Ok(None) | Ok(Some(StopReason::Reached)) | Ok(Some(StopReason::StartOfHistory)) => {}
+            Ok(Some(StopReason::Breakpoint(address))) => {
+                logger.breakpoint(address);
+                // A fresh run continues past the breakpoint the last one stopped at.
+                run = Run::new(RunTarget::Continue);
+            }
+            Ok(Some(StopReason::Watchpoint { hit, address })) => {
+                logger.watchpoint(&vm.describe_watchpoint_hit(&hit, address));
+            }
+            Ok(Some(StopReason::Halted)) => return 0,
+            Ok(Some(StopReason::Exited(code))) => return code,
+            Ok(Some(StopReason::Trap(trap))) => {
+                logger.trap_error(&trap);
+                return 1;
+            }
+            Ok(Some(StopReason::WaitingForInput)) => {
+                let mut line = String::new();
+
+                match input.read_line(&mut line) {
+                    Ok(0) | Err(_) => {
+                        eprintln!("Program is waiting for input, but standard input is closed");
+                        return 1;
+                    }
+                    Ok(_) => {
+                        if !line.ends_with('\n') {
+                            line.push('\n');
+                        }
+                        vm.push_input(&line);
+                    }
+                }
+            }
+            Err(err) => {
+                logger.fatal_error(&err);
+                return 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::breakpoints::{Breakpoint, BreakpointLocation};
+    use crate::runtime::loader::load_words;
+    use crate::runtime::vm::MemoryLayout;
+
+    #[test]
+    fn breakpoints_are_passed() {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        // Read an integer, print it and exit with it as the exit code.
+        load_words(
+            &mut vm,
+            &[
+                0x24020005, 0x0000000c, 0x00402021, 0x24020001, 0x0000000c, 0x24020011, 0x0000000c,
+            ],
+        );
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400004)));
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x0040000c)));
+
+        let mut output = vec![];
+        let code = run_with(&mut vm, &mut "42\n".as_bytes(), &mut output);
+
+        assert_eq!(code, 42);
+        assert_eq!(String::from_utf8(output).unwrap(), "42");
+        assert!(vm
+            .get_breakpoints()
+            .iter()
+            .all(|breakpoint| breakpoint.hits == 1));
+    }
+}
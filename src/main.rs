@@ -1,84 +1,506 @@
 #![allow(dead_code)]
 
 mod assembler;
+mod cli;
+mod headless;
 mod mips;
 mod runtime;
 mod term_ui;
 
+use std::path::Path;
+use std::time::{Duration, Instant};
+
 use term_ui::console::Console;
+use term_ui::speed::{Pacer, RunSpeed};
 use term_ui::VMState;
 
+use crate::mips::disassembler::disassemble;
+use crate::runtime::breakpoints::BreakpointLocation;
+use crate::runtime::coverage::Coverage;
+use crate::runtime::debug_info::DebugInfo;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::expression::Expression;
+use crate::runtime::loader::{load_program, new_vm, reset, Image, Program};
+use crate::runtime::profiler::Profiler;
+use crate::runtime::stepping::{Run, RunTarget, StopReason};
+use crate::runtime::trace::{self, Tracer};
 use crate::runtime::vm;
 
 fn main() {
-    let layout = vm::MemoryLayout::mars(0x1000, 0x1000);
-    let mut vm = vm::VM::new(layout);
+    let arguments = match cli::parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
 
-    let text_segment = vm.memory.mut_segment_by_name("text").unwrap();
-    text_segment.allow_writes();
+    if arguments.command == cli::Command::Help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-    {
-        let text_segment = vm.memory.segment_by_name("text").unwrap();
+    if arguments.command == cli::Command::TraceDiff {
+        let [first, second] = &arguments.trace_files[..] else {
+            unreachable!()
+        };
+
+        std::process::exit(trace_diff(first, second));
+    }
+
+    let image = match &arguments.program {
+        Some(path) => Image::Binary(std::fs::read(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", path, err);
+            std::process::exit(1);
+        })),
+        None => Image::Demo,
+    };
+    let program = Program {
+        image,
+        arguments: arguments.program_arguments,
+    };
+
+    let mut vm = new_vm();
+    vm.options = arguments.options;
+
+    if let Some(path) = &arguments.symbols {
+        vm.debug_info = load_symbols(path).unwrap_or_else(|err| {
+            eprintln!("Failed to read {}: {}", path, err);
+            std::process::exit(1);
+        });
+    }
 
-        let text_ptr = vm.memory.align_address(
-            text_segment.get_low_address(),
-            4,
-            runtime::memory::SegmentDirection::Up,
+    for watchpoint in arguments.watchpoints {
+        vm.memory.watchpoints.add(watchpoint);
+    }
+
+    for breakpoint in arguments.breakpoints {
+        vm.add_breakpoint(breakpoint);
+    }
+
+    if arguments.profile || arguments.profile_folded.is_some() {
+        vm.profiler = Some(Profiler::new());
+    }
+
+    if let Err(err) = load_program(&mut vm, &program) {
+        eprintln!(
+            "Failed to load {}: {}",
+            arguments.program.unwrap_or_default(),
+            err.message
         );
+        std::process::exit(1);
+    }
+
+    for location in vm.get_unresolved_breakpoints() {
+        if let BreakpointLocation::Line(line) = location {
+            if vm.debug_info.instruction_addresses().next().is_none() {
+                eprintln!(
+                    "Cannot break at line {} without line information from --symbols",
+                    line
+                );
+            } else {
+                eprintln!("Cannot break at line {}, as it has no instructions", line);
+            }
+            std::process::exit(2);
+        }
+    }
+
+    if arguments.coverage.is_some() {
+        vm.coverage = Some(Coverage::new(program_addresses(&vm, &program)));
+    }
+
+    if let Some(path) = &arguments.trace {
+        match std::fs::File::create(path) {
+            Ok(file) => {
+                let out = Box::new(std::io::BufWriter::new(file));
+                vm.tracer = Some(Tracer::new(out, arguments.trace_format));
+            }
+            Err(err) => {
+                eprintln!("Failed to create {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match arguments.command {
+        cli::Command::Run => {
+            let code = headless::run(&mut vm);
+
+            // Statistics go to standard error, so that they don't mix with program output.
+            if arguments.stats {
+                eprint!("\n{}", vm.statistics);
+            }
+
+            if let Some(profiler) = &vm.profiler {
+                if arguments.profile {
+                    eprint!("\n{}", profiler.report(&vm.debug_info));
+                }
+
+                if let Some(path) = &arguments.profile_folded {
+                    if let Err(err) = std::fs::write(path, profiler.folded(&vm.debug_info)) {
+                        eprintln!("Failed to write {}: {}", path, err);
+                    }
+                }
+            }
+
+            if let (Some(coverage), Some(path)) = (&vm.coverage, &arguments.coverage) {
+                let source_file = vm
+                    .debug_info
+                    .get_source_path()
+                    .or(arguments.program.as_deref())
+                    .unwrap_or_default();
+
+                match coverage.lcov(&vm, source_file) {
+                    Ok(lcov) => {
+                        if let Err(err) = std::fs::write(path, lcov) {
+                            eprintln!("Failed to write {}: {}", path, err);
+                        }
+                    }
+                    Err(err) => eprintln!("Cannot write {}: {}", path, err),
+                }
+
+                eprint!("\n{}", coverage.summary(&vm));
+            }
+
+            finish_trace(&mut vm, &arguments.trace);
+            std::process::exit(code)
+        }
+        cli::Command::Disassemble => {
+            if let Image::Binary(bytes) = &program.image {
+                print_disassembly(&vm, bytes.len());
+            }
+        }
+        _ => {
+            run_tui(&mut vm, &program, &arguments.watch_expressions);
+            finish_trace(&mut vm, &arguments.trace);
+        }
+    }
+}
+
+/// Flush the trace being written to `path`, if any, and report any error writing it.
+fn finish_trace(vm: &mut vm::VM, path: &Option<String>) {
+    if let (Some(tracer), Some(path)) = (&mut vm.tracer, path) {
+        if let Err(err) = tracer.finish() {
+            eprintln!("Failed to write {}: {}", path, err);
+        }
+    }
+}
+
+/// Compare two trace files and print where they first differ. Return the exit code: 0 if
+/// the traces match, 1 if they differ, or 2 if either could not be read.
+fn trace_diff(first: &str, second: &str) -> i32 {
+    let read = |path: &str| {
+        std::fs::read(path)
+            .map_err(|err| format!("Failed to read {}: {}", path, err))
+            .and_then(|data| trace::read_trace(&data).map_err(|err| format!("{}: {}", path, err)))
+    };
+
+    let (a, b) = match (read(first), read(second)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(message), _) | (_, Err(message)) => {
+            eprintln!("{}", message);
+            return 2;
+        }
+    };
+
+    match trace::first_divergence(&a, &b) {
+        None => {
+            println!("The traces match ({} steps)", a.len());
+            0
+        }
+        Some(trace::Divergence::Step(index)) => {
+            println!("The traces diverge at step {}", index + 1);
+
+            if let Some(previous) = index.checked_sub(1).map(|previous| &a[previous]) {
+                println!("  previous:  {}", previous);
+            }
+
+            println!("  {}:  {}", first, a[index]);
+            println!("  {}:  {}", second, b[index]);
+            1
+        }
+        Some(trace::Divergence::Length(steps)) => {
+            let (shorter, longer) = if a.len() < b.len() {
+                (first, second)
+            } else {
+                (second, first)
+            };
+
+            println!(
+                "{} ends after {} steps, while {} continues",
+                shorter, steps, longer
+            );
+            1
+        }
+    }
+}
+
+/// Print the disassembly of the `size` bytes of loaded program, one instruction per line.
+fn print_disassembly(vm: &vm::VM, size: usize) {
+    let start = match vm.memory.segment_by_name("text") {
+        Some(text) => text.get_low_address(),
+        None => return,
+    };
+
+    for address in (start..start + size).step_by(4) {
+        for label in vm.debug_info.symbols_at(address) {
+            println!("{}:", label);
+        }
+
+        let code = match vm.memory.get_word(address) {
+            Ok(code) => code,
+            Err(_) => break,
+        };
+
+        let text = match vm.decode_instruction(code) {
+            Ok(instruction) => disassemble(&instruction, address as u32, |target| {
+                vm.debug_info.symbols_at(target as usize).next()
+            })
+            .to_string(),
+            Err(_) => format!(".word {:#010x}", code),
+        };
 
-        vm.memory.set_word(text_ptr, 0x2409ffff);
-        vm.memory.set_word(text_ptr + 4, 0x240a0002);
-        vm.memory.set_word(text_ptr + 8, 0x012a0018);
-        vm.set_pc(text_ptr);
+        println!("  {:#010x}  {:08x}  {}", address, code, text);
     }
+}
+
+/// The addresses of the loaded program's instructions: those with source lines if it was
+/// assembled, or otherwise every word of a binary image other than null instructions, which
+/// end the program rather than run.
+fn program_addresses(vm: &vm::VM, program: &Program) -> Vec<usize> {
+    let addresses: Vec<usize> = vm.debug_info.instruction_addresses().collect();
+
+    match (&program.image, vm.memory.segment_by_name("text")) {
+        (Image::Binary(bytes), Some(text)) if addresses.is_empty() => {
+            let start = text.get_low_address();
+            (start..start + bytes.len())
+                .step_by(4)
+                .filter(|address| vm.memory.get_word(*address).is_ok_and(|word| word != 0))
+                .collect()
+        }
+        _ => addresses,
+    }
+}
+
+/// Read a symbol file, and the source file it names. The path of the source file is
+/// relative to the symbol file.
+fn load_symbols(path: &str) -> Result<DebugInfo, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut debug_info = DebugInfo::parse(&text)?;
+
+    if let Some(source_path) = debug_info.get_source_path() {
+        let source_path = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(source_path)
+            .to_string_lossy()
+            .into_owned();
+        let source = std::fs::read_to_string(&source_path)
+            .map_err(|err| format!("Failed to read {}: {}", source_path, err))?;
+
+        debug_info.set_source(&source);
+        debug_info.set_source_path(&source_path);
+    }
+
+    Ok(debug_info)
+}
+
+/// The file the console is saved to, in the working directory.
+const CONSOLE_SAVE_PATH: &str = "juno-console.txt";
+
+/// The time between redraws of the terminal UI.
+const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// The number of instructions executed between checks of the frame deadline when running at
+/// unlimited speed.
+const UNLIMITED_BATCH_SIZE: usize = 1000;
+
+fn run_tui(vm: &mut vm::VM, program: &Program, watch_expressions: &[Expression]) {
     let mut console = Console::new();
-    let mut paused = true;
     let mut halted = false;
 
+    // The run in progress, or None while paused.
+    let mut run: Option<Run> = None;
+
+    let mut speed = RunSpeed::unlimited();
+    let mut pacer = Pacer::new(FRAME_INTERVAL);
+    let mut last_frame = Instant::now();
+
     let mut ui = term_ui::make_crossterm_viewer();
     ui.init().unwrap();
 
     loop {
-        if !paused && !halted {
-            let instruction = vm.run_single_instruction();
-
-            match instruction {
-                Ok((instruction_data, trap)) => {
-                    if instruction_data.is_null() {
-                        halted = true;
-                        console.execution_finished("Null instruction reached");
-                    }
+        let frame_start = Instant::now();
+        let elapsed = frame_start - last_frame;
+        let deadline = frame_start + FRAME_INTERVAL;
+        last_frame = frame_start;
 
-                    if let Some(trap) = trap {
-                        halted = true;
-                        console.trap_error(&trap.message);
-                    }
+        // While a read syscall waits for input, the VM is held until the input is submitted.
+        if let Some(current) = run.as_mut().filter(|_| vm.get_input_request().is_none()) {
+            match run_frame(vm, current, speed, &mut pacer, elapsed, deadline) {
+                Ok(None) | Ok(Some(StopReason::WaitingForInput)) => {}
+                Ok(Some(StopReason::Reached)) | Ok(Some(StopReason::StartOfHistory)) => run = None,
+                Ok(Some(StopReason::Breakpoint(address))) => {
+                    run = None;
+                    console.paused(&format!("Breakpoint at {:#010x}", address));
+                }
+                Ok(Some(StopReason::Watchpoint { hit, address })) => {
+                    run = None;
+                    console.paused(&format!(
+                        "Watchpoint: {}",
+                        vm.describe_watchpoint_hit(&hit, address)
+                    ));
+                }
+                Ok(Some(StopReason::Halted)) => {
+                    halted = true;
+                    console.execution_finished("Null instruction reached");
+                }
+                Ok(Some(StopReason::Exited(code))) => {
+                    halted = true;
+                    console.execution_finished(&format!("Program exited with code {}", code));
+                }
+                Ok(Some(StopReason::Trap(trap))) => {
+                    halted = true;
+                    console.trap_error(&trap.message);
                 }
                 Err(err) => {
                     halted = true;
-
                     console.runtime_error(&err);
                 }
             }
+
+            if halted {
+                run = None;
+            }
+
+            console.output(&vm.take_output());
+
+            for warning in vm.take_warnings() {
+                console.warning(&warning.message);
+            }
         }
 
         match ui.update(
             &(VMState {
-                vm: &vm,
-                paused,
+                vm,
+                paused: run.is_none(),
                 halted,
+                speed,
                 console: &console,
+                watch_expressions,
             }),
+            deadline.saturating_duration_since(Instant::now()),
         ) {
             Ok(term_ui::VMViewerEvent::Quit) => break,
             Ok(term_ui::VMViewerEvent::None) => {}
+            Ok(term_ui::VMViewerEvent::Faster) => speed = speed.faster(),
+            Ok(term_ui::VMViewerEvent::Slower) => speed = speed.slower(),
             Ok(term_ui::VMViewerEvent::TogglePause) => {
                 if !halted {
-                    paused = !paused;
+                    run = match run {
+                        Some(_) => None,
+                        None => Some(Run::new(RunTarget::Continue)),
+                    };
+                }
+            }
+            Ok(term_ui::VMViewerEvent::Step) => start_run(&mut run, halted, RunTarget::Step),
+            Ok(term_ui::VMViewerEvent::StepOver) => {
+                start_run(&mut run, halted, RunTarget::StepOver)
+            }
+            Ok(term_ui::VMViewerEvent::StepOut) => start_run(&mut run, halted, RunTarget::StepOut),
+            Ok(term_ui::VMViewerEvent::StepBack) => {
+                if run.is_none() {
+                    match vm.step_back() {
+                        Ok(true) => halted = false,
+                        Ok(false) => console.paused("Reached the start of the recorded history"),
+                        Err(err) => console.runtime_error(&err),
+                    }
+                }
+            }
+            Ok(term_ui::VMViewerEvent::RunBackward) => {
+                if run.is_none() {
+                    let recorded = vm.get_journal().len();
+
+                    match vm.run_backward(usize::MAX) {
+                        Ok(Some(StopReason::Breakpoint(address))) => {
+                            console.paused(&format!("Breakpoint at {:#010x}", address))
+                        }
+                        Ok(_) => console.paused("Reached the start of the recorded history"),
+                        Err(err) => console.runtime_error(&err),
+                    }
+
+                    if vm.get_journal().len() != recorded {
+                        halted = false;
+                    }
+                }
+            }
+            Ok(term_ui::VMViewerEvent::Reset) => {
+                run = None;
+                halted = false;
+                console.reset();
+
+                if let Err(err) = reset(vm, program) {
+                    halted = true;
+                    console.runtime_error(&err);
                 }
             }
+            Ok(term_ui::VMViewerEvent::ToggleBreakpoint(location)) => {
+                vm.toggle_breakpoint(location);
+            }
+            Ok(term_ui::VMViewerEvent::SaveConsole) => {
+                match console.save(std::path::Path::new(CONSOLE_SAVE_PATH)) {
+                    Ok(()) => console.info(&format!("Console saved to {}", CONSOLE_SAVE_PATH)),
+                    Err(err) => console.warning(&format!("Failed to save the console: {}", err)),
+                }
+            }
+            Ok(term_ui::VMViewerEvent::SubmitInput(input)) => {
+                console.input(input.trim_end_matches('\n'));
+                vm.push_input(&input);
+            }
             Err(e) => panic!("Failed to update UI: {}", e),
         }
     }
     ui.exit().expect("Failed to exit UI");
 }
+
+/// Continue a run for one frame. Runs to a breakpoint execute at `speed`, paced by `pacer`
+/// for the `elapsed` time, while steps complete as fast as possible.
+/// Execution stops at `deadline`, so that the UI keeps redrawing however fast the VM runs.
+fn run_frame(
+    vm: &mut vm::VM,
+    run: &mut Run,
+    speed: RunSpeed,
+    pacer: &mut Pacer,
+    elapsed: Duration,
+    deadline: Instant,
+) -> Result<Option<StopReason>, RuntimeError> {
+    let rate = match run.get_target() {
+        RunTarget::Continue => speed.instructions_per_second(),
+        _ => None,
+    };
+
+    if let Some(rate) = rate {
+        let steps = pacer.steps(rate, elapsed);
+
+        return if steps > 0 {
+            vm.run(run, steps)
+        } else {
+            Ok(None)
+        };
+    }
+
+    loop {
+        let result = vm.run(run, UNLIMITED_BATCH_SIZE)?;
+
+        if result.is_some() || Instant::now() >= deadline {
+            return Ok(result);
+        }
+    }
+}
+
+/// Start a run towards `target`, if the VM is paused and has not halted.
+fn start_run(run: &mut Option<Run>, halted: bool, target: RunTarget) {
+    if run.is_none() && !halted {
+        *run = Some(Run::new(target));
+    }
+}
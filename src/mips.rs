@@ -1 +1,2 @@
-pub mod instruction;
\ No newline at end of file
+pub mod disassembler;
+pub mod instruction;
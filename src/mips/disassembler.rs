@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::mips::instruction::{InstructionArgs, InstructionData};
+use crate::runtime::register_aliases::Register;
+
+/// An instruction in assembly syntax, such as `lw $t0, 4($sp)`.
+pub struct Disassembly {
+    pub mnemonic: String,
+    pub operands: String,
+    /// The address a branch or jump with a fixed target goes to.
+    pub target: Option<u32>,
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+fn gpr(register: u8) -> String {
+    Register::General(register & 31).to_string()
+}
+
+fn fpr(register: u8) -> String {
+    Register::Float(register).to_string()
+}
+
+/// Format a memory operand, such as `-8($sp)`.
+fn memory(offset: u16, base: u8) -> String {
+    format!("{}({})", offset as i16, gpr(base))
+}
+
+/// Prefix the operands of a floating point compare or branch with the condition flag it uses,
+/// which is left out for flag 0 as assemblers do.
+fn with_flag(flag: u8, operands: String) -> String {
+    if flag == 0 {
+        operands
+    } else if operands.is_empty() {
+        flag.to_string()
+    } else {
+        format!("{}, {}", flag, operands)
+    }
+}
+
+/// Disassemble the instruction at `address`. Branch and jump targets are shown with the label
+/// `symbol_at` gives for them, or as an address if there is none.
+pub fn disassemble<'a>(
+    instruction: &InstructionData,
+    address: u32,
+    symbol_at: impl Fn(u32) -> Option<&'a str>,
+) -> Disassembly {
+    let name = instruction.base.name;
+
+    if instruction.is_null() {
+        return Disassembly {
+            mnemonic: String::from("nop"),
+            operands: String::new(),
+            target: None,
+        };
+    }
+
+    let mut target = None;
+    let mut label = |destination: u32| {
+        target = Some(destination);
+
+        match symbol_at(destination) {
+            Some(symbol) => symbol.to_owned(),
+            None => format!("{:#010x}", destination),
+        }
+    };
+
+    let operands = match instruction.args {
+        InstructionArgs::RFormat(args) => {
+            let (rs, rt, rd) = (gpr(args.rs), gpr(args.rt), gpr(args.rd));
+
+            // Floating point instructions use the rt, rd and shamt fields as ft, fs and fd.
+            let (ft, fs, fd) = (fpr(args.rt), fpr(args.rd), fpr(args.shamt));
+
+            match name {
+                "sll" | "srl" | "sra" | "rotr" => format!("{}, {}, {}", rd, rt, args.shamt),
+                "sllv" | "srlv" | "srav" | "rotrv" => format!("{}, {}, {}", rd, rt, rs),
+                "mult" | "multu" | "div" | "divu" | "madd" | "maddu" | "msub" | "msubu" => {
+                    format!("{}, {}", rs, rt)
+                }
+                "mfhi" | "mflo" => rd,
+                "mthi" | "mtlo" | "jr" => rs,
+                "jalr" if args.rd == 31 => rs,
+                "jalr" => format!("{}, {}", rd, rs),
+                "syscall" => String::new(),
+                "clz" | "clo" => format!("{}, {}", rd, rs),
+                "seb" | "seh" | "wsbh" => format!("{}, {}", rd, rt),
+                "ext" => format!("{}, {}, {}, {}", rt, rs, args.shamt, args.rd + 1),
+                "ins" => format!(
+                    "{}, {}, {}, {}",
+                    rt,
+                    rs,
+                    args.shamt,
+                    (args.rd + 1).saturating_sub(args.shamt)
+                ),
+                // The condition flag is held in the upper three bits of rt.
+                "movf" | "movt" => format!("{}, {}, {}", rd, rs, args.rt >> 2),
+                "mfc1" | "mtc1" => format!("{}, {}", rt, fs),
+                _ if name.starts_with("c.") => {
+                    // The condition flag is held in the upper three bits of fd.
+                    with_flag(args.shamt >> 2, format!("{}, {}", fs, ft))
+                }
+                _ if name.starts_with("movf.") || name.starts_with("movt.") => {
+                    format!("{}, {}, {}", fd, fs, args.rt >> 2)
+                }
+                _ if name.starts_with("movz.") || name.starts_with("movn.") => {
+                    format!("{}, {}, {}", fd, fs, rt)
+                }
+                _ if ["add.", "sub.", "mul.", "div."]
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix)) =>
+                {
+                    format!("{}, {}, {}", fd, fs, ft)
+                }
+                _ if name.contains('.') => format!("{}, {}", fd, fs),
+                _ => format!("{}, {}, {}", rd, rs, rt),
+            }
+        }
+        InstructionArgs::IFormat(args) => {
+            let (rs, rt) = (gpr(args.rs), gpr(args.rt));
+            let branch_target = address
+                .wrapping_add(4)
+                .wrapping_add(((args.imm as i16 as i32) << 2) as u32);
+
+            match name {
+                "andi" | "ori" | "xori" => format!("{}, {}, {:#x}", rt, rs, args.imm),
+                "lui" => format!("{}, {:#x}", rt, args.imm),
+                "beq" | "bne" => format!("{}, {}, {}", rs, rt, label(branch_target)),
+                "blez" | "bgtz" => format!("{}, {}", rs, label(branch_target)),
+                // The condition flag is held in the upper three bits of rt.
+                "bc1f" | "bc1t" => with_flag(args.rt >> 2, label(branch_target)),
+                "lwc1" | "swc1" | "ldc1" | "sdc1" => {
+                    format!("{}, {}", fpr(args.rt), memory(args.imm, args.rs))
+                }
+                "lb" | "lbu" | "lh" | "lhu" | "lw" | "sb" | "sh" | "sw" | "lwl" | "lwr" | "swl"
+                | "swr" | "ll" | "sc" => format!("{}, {}", rt, memory(args.imm, args.rs)),
+                _ => format!("{}, {}, {}", rt, rs, args.imm as i16),
+            }
+        }
+        InstructionArgs::JFormat(args) => {
+            // The target replaces the low 28 bits of the address of the next instruction.
+            label((address.wrapping_add(4) & 0xf000_0000) | (args.address << 2))
+        }
+    };
+
+    Disassembly {
+        mnemonic: name.to_owned(),
+        operands,
+        target,
+    }
+}
@@ -20,6 +20,7 @@ pub struct JFormat {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
 pub enum InstructionArgs {
     RFormat(RFormat),
     IFormat(IFormat),
@@ -67,18 +68,81 @@ impl<'a> InstructionData<'a> {
     /// Returns true if the instruction is a sll instruction with all zero arguments.
     pub fn is_null(&self) -> bool {
         match self.args {
-            InstructionArgs::RFormat(r_args) => {
-                if self.base.opc_func == 0 {
-                    r_args.rs == 0 && r_args.rt == 0 && r_args.rd == 0 && r_args.shamt == 0
-                } else {
-                    false
-                }
+            InstructionArgs::RFormat(r_args)
+                if self.base.special == SPECIAL && self.base.opc_func == 0 =>
+            {
+                r_args.rs == 0 && r_args.rt == 0 && r_args.rd == 0 && r_args.shamt == 0
             }
             _ => false,
         }
     }
 }
 
+/// Revisions of the MIPS instruction set, from oldest to newest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Isa {
+    /// The original MIPS-I instruction set, as used in most introductory courses.
+    MipsI,
+    /// MIPS32 Release 1, which adds the SPECIAL2 instructions, `ll`/`sc` and conditional moves
+    /// among others.
+    Mips32,
+    /// MIPS32 Release 2, which adds the SPECIAL3 instructions and rotates.
+    Mips32R2,
+}
+
+impl Isa {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Isa::MipsI => "MIPS-I",
+            Isa::Mips32 => "MIPS32",
+            Isa::Mips32R2 => "MIPS32 Release 2",
+        }
+    }
+}
+
+/// Opcodes under which R-format instructions are further identified by their function code.
+pub const SPECIAL: u8 = 0b000000;
+pub const SPECIAL2: u8 = 0b011100;
+pub const SPECIAL3: u8 = 0b011111;
+/// The opcode of coprocessor 1 (floating point) instructions.
+pub const COP1: u8 = 0b010001;
+
+/// Values of the `fmt` field of coprocessor 1 instructions, which occupies the rs field.
+pub const FMT_S: u8 = 16;
+pub const FMT_D: u8 = 17;
+pub const FMT_W: u8 = 20;
+/// Values of the rs field for coprocessor 1 moves and branches.
+pub const COP1_MF: u8 = 0;
+pub const COP1_MT: u8 = 4;
+pub const COP1_BC: u8 = 8;
+
+/// A register or shift amount field of an R-format instruction.
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Rs,
+    Rt,
+    Rd,
+    Shamt,
+    /// The lowest bit of the rt field, which selects between the true and false variants of
+    /// instructions that test a floating point condition flag.
+    Tf,
+}
+
+impl Field {
+    /// Extract this field from a four byte instruction code.
+    pub fn extract(&self, instruction: u32) -> u8 {
+        let (shift, mask) = match self {
+            Field::Rs => (21, 0b11111),
+            Field::Rt => (16, 0b11111),
+            Field::Rd => (11, 0b11111),
+            Field::Shamt => (6, 0b11111),
+            Field::Tf => (16, 0b1),
+        };
+
+        ((instruction >> shift) & mask) as u8
+    }
+}
+
 #[derive(Debug)]
 pub struct Instruction<'a> {
     /// The opcode or function code of the instruction. If the instruction is
@@ -86,303 +150,1116 @@ pub struct Instruction<'a> {
     pub opc_func: u8,
     pub name: &'a str,
     pub format: InstructionFormat,
+    /// For R-format instructions, the opcode that `opc_func` is a function code of; one of
+    /// `SPECIAL`, `SPECIAL2` or `SPECIAL3`. Ignored for other formats.
+    pub special: u8,
+    /// Fields that must hold the given values for an instruction code to decode as this
+    /// instruction. Used to tell apart instructions that share an opcode and function code,
+    /// such as `srl` and `rotr`, or `add.s` and `add.d`.
+    pub discriminator: &'a [(Field, u8)],
+    /// The earliest revision of the instruction set that includes this instruction.
+    pub isa: Isa,
+}
+
+impl<'a> Instruction<'a> {
+    /// Returns true if the given four byte instruction code is an encoding of this instruction.
+    pub fn matches(&self, instruction: u32) -> bool {
+        let opcode = (instruction >> 26) as u8;
+        let func_code = (instruction & 0b111111) as u8;
+
+        let encoding_matches = match self.format {
+            InstructionFormat::R => opcode == self.special && func_code == self.opc_func,
+            _ => opcode == self.opc_func,
+        };
+
+        encoding_matches
+            && self
+                .discriminator
+                .iter()
+                .all(|(field, value)| field.extract(instruction) == *value)
+    }
 }
 
 /// Supported MIPS instructions.
 pub mod instructions {
     use super::*;
 
+    /// Defaults for instructions in the original instruction set, which are told apart by their
+    /// opcode and function code alone.
+    const MIPS_I: Instruction = Instruction {
+        opc_func: 0,
+        name: "",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::MipsI,
+    };
+
     pub const ADD: Instruction = Instruction {
         opc_func: 0b100000,
         name: "add",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const ADDU: Instruction = Instruction {
         opc_func: 0b100001,
         name: "addu",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const ADDI: Instruction = Instruction {
         opc_func: 0b001000,
         name: "addi",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const ADDIU: Instruction = Instruction {
         opc_func: 0b001001,
         name: "addiu",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const AND: Instruction = Instruction {
         opc_func: 0b100100,
         name: "and",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const ANDI: Instruction = Instruction {
         opc_func: 0b001100,
         name: "andi",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const DIV: Instruction = Instruction {
         opc_func: 0b011010,
         name: "div",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const DIVU: Instruction = Instruction {
         opc_func: 0b011011,
         name: "divu",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const MULT: Instruction = Instruction {
         opc_func: 0b011000,
         name: "mult",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const MULTU: Instruction = Instruction {
         opc_func: 0b011001,
         name: "multu",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const NOR: Instruction = Instruction {
         opc_func: 0b100111,
         name: "nor",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const OR: Instruction = Instruction {
         opc_func: 0b100101,
         name: "or",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const ORI: Instruction = Instruction {
         opc_func: 0b001101,
         name: "ori",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SLL: Instruction = Instruction {
         opc_func: 0b000000,
         name: "sll",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SLLV: Instruction = Instruction {
         opc_func: 0b000100,
         name: "sllv",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SRA: Instruction = Instruction {
         opc_func: 0b000011,
         name: "sra",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SRAV: Instruction = Instruction {
         opc_func: 0b000111,
         name: "srav",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SRL: Instruction = Instruction {
         opc_func: 0b000010,
         name: "srl",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SRLV: Instruction = Instruction {
         opc_func: 0b000110,
         name: "srlv",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SUB: Instruction = Instruction {
         opc_func: 0b100010,
         name: "sub",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SUBU: Instruction = Instruction {
         opc_func: 0b100011,
         name: "subu",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const XOR: Instruction = Instruction {
         opc_func: 0b100110,
         name: "xor",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const XORI: Instruction = Instruction {
         opc_func: 0b001110,
         name: "xori",
         format: InstructionFormat::I,
+        ..MIPS_I
+    };
+
+    pub const LUI: Instruction = Instruction {
+        opc_func: 0b001111,
+        name: "lui",
+        format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SLT: Instruction = Instruction {
         opc_func: 0b101010,
         name: "slt",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SLTU: Instruction = Instruction {
         opc_func: 0b101001,
         name: "sltu",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SLTI: Instruction = Instruction {
         opc_func: 0b001010,
         name: "slti",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SLTIU: Instruction = Instruction {
-        opc_func: 0b001001,
+        opc_func: 0b001011,
         name: "sltiu",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const BEQ: Instruction = Instruction {
         opc_func: 0b000100,
         name: "beq",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const BGTZ: Instruction = Instruction {
         opc_func: 0b000111,
-        name: "bgz",
+        name: "bgtz",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const BLEZ: Instruction = Instruction {
         opc_func: 0b000110,
         name: "blez",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const BNE: Instruction = Instruction {
         opc_func: 0b000101,
         name: "bne",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const J: Instruction = Instruction {
         opc_func: 0b000010,
         name: "j",
         format: InstructionFormat::J,
+        ..MIPS_I
     };
 
     pub const JAL: Instruction = Instruction {
         opc_func: 0b000011,
         name: "jal",
         format: InstructionFormat::J,
+        ..MIPS_I
     };
 
     pub const JALR: Instruction = Instruction {
         opc_func: 0b001001,
         name: "jalr",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const JR: Instruction = Instruction {
         opc_func: 0b001000,
         name: "jr",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const LB: Instruction = Instruction {
         opc_func: 0b100000,
         name: "lb",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const LBU: Instruction = Instruction {
         opc_func: 0b100100,
         name: "lbu",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const LH: Instruction = Instruction {
         opc_func: 0b100001,
         name: "lh",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const LHU: Instruction = Instruction {
         opc_func: 0b100101,
         name: "lhu",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const LW: Instruction = Instruction {
         opc_func: 0b100011,
         name: "lw",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SB: Instruction = Instruction {
         opc_func: 0b101000,
         name: "sb",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SH: Instruction = Instruction {
         opc_func: 0b101001,
         name: "sh",
         format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const SW: Instruction = Instruction {
         opc_func: 0b101011,
         name: "sw",
         format: InstructionFormat::I,
+        ..MIPS_I
+    };
+
+    pub const LWL: Instruction = Instruction {
+        opc_func: 0b100010,
+        name: "lwl",
+        format: InstructionFormat::I,
+        ..MIPS_I
+    };
+
+    pub const LWR: Instruction = Instruction {
+        opc_func: 0b100110,
+        name: "lwr",
+        format: InstructionFormat::I,
+        ..MIPS_I
+    };
+
+    pub const SWL: Instruction = Instruction {
+        opc_func: 0b101010,
+        name: "swl",
+        format: InstructionFormat::I,
+        ..MIPS_I
+    };
+
+    pub const SWR: Instruction = Instruction {
+        opc_func: 0b101110,
+        name: "swr",
+        format: InstructionFormat::I,
+        ..MIPS_I
     };
 
     pub const MFHI: Instruction = Instruction {
         opc_func: 0b010000,
         name: "mfhi",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const MFLO: Instruction = Instruction {
         opc_func: 0b010010,
         name: "mflo",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const MTHI: Instruction = Instruction {
         opc_func: 0b010001,
         name: "mthi",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const MTLO: Instruction = Instruction {
         opc_func: 0b010011,
         name: "mtlo",
         format: InstructionFormat::R,
+        ..MIPS_I
     };
 
     pub const SYSCALL: Instruction = Instruction {
         opc_func: 0b001100,
         name: "syscall",
         format: InstructionFormat::R,
+        ..MIPS_I
+    };
+
+    /* MIPS32 additions */
+
+    pub const LL: Instruction = Instruction {
+        opc_func: 0b110000,
+        name: "ll",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const SC: Instruction = Instruction {
+        opc_func: 0b111000,
+        name: "sc",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MUL: Instruction = Instruction {
+        opc_func: 0b000010,
+        name: "mul",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MADD: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "madd",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MADDU: Instruction = Instruction {
+        opc_func: 0b000001,
+        name: "maddu",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MSUB: Instruction = Instruction {
+        opc_func: 0b000100,
+        name: "msub",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MSUBU: Instruction = Instruction {
+        opc_func: 0b000101,
+        name: "msubu",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const CLZ: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "clz",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const CLO: Instruction = Instruction {
+        opc_func: 0b100001,
+        name: "clo",
+        format: InstructionFormat::R,
+        special: SPECIAL2,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVN: Instruction = Instruction {
+        opc_func: 0b001011,
+        name: "movn",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVZ: Instruction = Instruction {
+        opc_func: 0b001010,
+        name: "movz",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    /* MIPS32 Release 2 additions */
+
+    pub const SEB: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "seb",
+        format: InstructionFormat::R,
+        special: SPECIAL3,
+        discriminator: &[(Field::Shamt, 0b10000)],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const SEH: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "seh",
+        format: InstructionFormat::R,
+        special: SPECIAL3,
+        discriminator: &[(Field::Shamt, 0b11000)],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const WSBH: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "wsbh",
+        format: InstructionFormat::R,
+        special: SPECIAL3,
+        discriminator: &[(Field::Shamt, 0b00010)],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const EXT: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "ext",
+        format: InstructionFormat::R,
+        special: SPECIAL3,
+        discriminator: &[],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const INS: Instruction = Instruction {
+        opc_func: 0b000100,
+        name: "ins",
+        format: InstructionFormat::R,
+        special: SPECIAL3,
+        discriminator: &[],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const ROTR: Instruction = Instruction {
+        opc_func: 0b000010,
+        name: "rotr",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[(Field::Rs, 1)],
+        isa: Isa::Mips32R2,
+    };
+
+    pub const ROTRV: Instruction = Instruction {
+        opc_func: 0b000110,
+        name: "rotrv",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[(Field::Shamt, 1)],
+        isa: Isa::Mips32R2,
+    };
+
+    /* Coprocessor 1 (floating point) */
+
+    pub const ADD_S: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "add.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const ADD_D: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "add.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const SUB_S: Instruction = Instruction {
+        opc_func: 0b000001,
+        name: "sub.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const SUB_D: Instruction = Instruction {
+        opc_func: 0b000001,
+        name: "sub.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MUL_S: Instruction = Instruction {
+        opc_func: 0b000010,
+        name: "mul.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MUL_D: Instruction = Instruction {
+        opc_func: 0b000010,
+        name: "mul.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const DIV_S: Instruction = Instruction {
+        opc_func: 0b000011,
+        name: "div.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const DIV_D: Instruction = Instruction {
+        opc_func: 0b000011,
+        name: "div.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const SQRT_S: Instruction = Instruction {
+        opc_func: 0b000100,
+        name: "sqrt.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const SQRT_D: Instruction = Instruction {
+        opc_func: 0b000100,
+        name: "sqrt.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const ABS_S: Instruction = Instruction {
+        opc_func: 0b000101,
+        name: "abs.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const ABS_D: Instruction = Instruction {
+        opc_func: 0b000101,
+        name: "abs.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MOV_S: Instruction = Instruction {
+        opc_func: 0b000110,
+        name: "mov.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MOV_D: Instruction = Instruction {
+        opc_func: 0b000110,
+        name: "mov.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const NEG_S: Instruction = Instruction {
+        opc_func: 0b000111,
+        name: "neg.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const NEG_D: Instruction = Instruction {
+        opc_func: 0b000111,
+        name: "neg.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const ROUND_W_S: Instruction = Instruction {
+        opc_func: 0b001100,
+        name: "round.w.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const ROUND_W_D: Instruction = Instruction {
+        opc_func: 0b001100,
+        name: "round.w.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const TRUNC_W_S: Instruction = Instruction {
+        opc_func: 0b001101,
+        name: "trunc.w.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const TRUNC_W_D: Instruction = Instruction {
+        opc_func: 0b001101,
+        name: "trunc.w.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const CEIL_W_S: Instruction = Instruction {
+        opc_func: 0b001110,
+        name: "ceil.w.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const CEIL_W_D: Instruction = Instruction {
+        opc_func: 0b001110,
+        name: "ceil.w.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const FLOOR_W_S: Instruction = Instruction {
+        opc_func: 0b001111,
+        name: "floor.w.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const FLOOR_W_D: Instruction = Instruction {
+        opc_func: 0b001111,
+        name: "floor.w.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVF_S: Instruction = Instruction {
+        opc_func: 0b010001,
+        name: "movf.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S), (Field::Tf, 0)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVF_D: Instruction = Instruction {
+        opc_func: 0b010001,
+        name: "movf.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D), (Field::Tf, 0)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVT_S: Instruction = Instruction {
+        opc_func: 0b010001,
+        name: "movt.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S), (Field::Tf, 1)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVT_D: Instruction = Instruction {
+        opc_func: 0b010001,
+        name: "movt.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D), (Field::Tf, 1)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVZ_S: Instruction = Instruction {
+        opc_func: 0b010010,
+        name: "movz.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVZ_D: Instruction = Instruction {
+        opc_func: 0b010010,
+        name: "movz.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVN_S: Instruction = Instruction {
+        opc_func: 0b010011,
+        name: "movn.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVN_D: Instruction = Instruction {
+        opc_func: 0b010011,
+        name: "movn.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::Mips32,
+    };
+
+    pub const CVT_S_D: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "cvt.s.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const CVT_S_W: Instruction = Instruction {
+        opc_func: 0b100000,
+        name: "cvt.s.w",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_W)],
+        isa: Isa::MipsI,
+    };
+
+    pub const CVT_D_S: Instruction = Instruction {
+        opc_func: 0b100001,
+        name: "cvt.d.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const CVT_D_W: Instruction = Instruction {
+        opc_func: 0b100001,
+        name: "cvt.d.w",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_W)],
+        isa: Isa::MipsI,
+    };
+
+    pub const CVT_W_S: Instruction = Instruction {
+        opc_func: 0b100100,
+        name: "cvt.w.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const CVT_W_D: Instruction = Instruction {
+        opc_func: 0b100100,
+        name: "cvt.w.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_EQ_S: Instruction = Instruction {
+        opc_func: 0b110010,
+        name: "c.eq.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_EQ_D: Instruction = Instruction {
+        opc_func: 0b110010,
+        name: "c.eq.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_LT_S: Instruction = Instruction {
+        opc_func: 0b111100,
+        name: "c.lt.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_LT_D: Instruction = Instruction {
+        opc_func: 0b111100,
+        name: "c.lt.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_LE_S: Instruction = Instruction {
+        opc_func: 0b111110,
+        name: "c.le.s",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_S)],
+        isa: Isa::MipsI,
+    };
+
+    pub const C_LE_D: Instruction = Instruction {
+        opc_func: 0b111110,
+        name: "c.le.d",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, FMT_D)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MFC1: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "mfc1",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, COP1_MF)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MTC1: Instruction = Instruction {
+        opc_func: 0b000000,
+        name: "mtc1",
+        format: InstructionFormat::R,
+        special: COP1,
+        discriminator: &[(Field::Rs, COP1_MT)],
+        isa: Isa::MipsI,
+    };
+
+    pub const MOVF: Instruction = Instruction {
+        opc_func: 0b000001,
+        name: "movf",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[(Field::Tf, 0)],
+        isa: Isa::Mips32,
+    };
+
+    pub const MOVT: Instruction = Instruction {
+        opc_func: 0b000001,
+        name: "movt",
+        format: InstructionFormat::R,
+        special: SPECIAL,
+        discriminator: &[(Field::Tf, 1)],
+        isa: Isa::Mips32,
+    };
+
+    pub const BC1F: Instruction = Instruction {
+        opc_func: COP1,
+        name: "bc1f",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[(Field::Rs, COP1_BC), (Field::Tf, 0)],
+        isa: Isa::MipsI,
+    };
+
+    pub const BC1T: Instruction = Instruction {
+        opc_func: COP1,
+        name: "bc1t",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[(Field::Rs, COP1_BC), (Field::Tf, 1)],
+        isa: Isa::MipsI,
+    };
+
+    pub const LWC1: Instruction = Instruction {
+        opc_func: 0b110001,
+        name: "lwc1",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::MipsI,
+    };
+
+    pub const SWC1: Instruction = Instruction {
+        opc_func: 0b111001,
+        name: "swc1",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::MipsI,
+    };
+
+    pub const LDC1: Instruction = Instruction {
+        opc_func: 0b110101,
+        name: "ldc1",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
+    };
+
+    pub const SDC1: Instruction = Instruction {
+        opc_func: 0b111101,
+        name: "sdc1",
+        format: InstructionFormat::I,
+        special: SPECIAL,
+        discriminator: &[],
+        isa: Isa::Mips32,
     };
 
-    pub const ALL_INSTRUCTIONS: [Instruction; 48] = [
-        ADD, ADDU, ADDI, ADDIU, AND, ANDI, DIV, DIVU, MULT, MULTU, NOR, OR, ORI, SLL, SLLV, SRA,
-        SRAV, SRL, SRLV, SUB, SUBU, XOR, XORI, SLT, SLTU, SLTI, SLTIU, BEQ, BGTZ, BLEZ, BNE, J,
-        JAL, JALR, JR, LB, LBU, LH, LHU, LW, SB, SH, SW, MFHI, MFLO, MTHI, MTLO, SYSCALL,
+    /// All supported instructions. Instructions with a discriminator are listed before
+    /// instructions sharing the same encoding without one, so that decoding finds the more
+    /// specific match first.
+    pub const ALL_INSTRUCTIONS: [Instruction; 125] = [
+        ROTR, ROTRV, ADD, ADDU, ADDI, ADDIU, AND, ANDI, DIV, DIVU, MULT, MULTU, NOR, OR, ORI, SLL,
+        SLLV, SRA, SRAV, SRL, SRLV, SUB, SUBU, XOR, XORI, LUI, SLT, SLTU, SLTI, SLTIU, BEQ, BGTZ,
+        BLEZ, BNE, J, JAL, JALR, JR, LB, LBU, LH, LHU, LW, SB, SH, SW, LWL, LWR, SWL, SWR, MFHI,
+        MFLO, MTHI, MTLO, SYSCALL, LL, SC, MUL, MADD, MADDU, MSUB, MSUBU, CLZ, CLO, MOVN, MOVZ,
+        SEB, SEH, WSBH, EXT, INS, ADD_S, ADD_D, SUB_S, SUB_D, MUL_S, MUL_D, DIV_S, DIV_D, SQRT_S,
+        SQRT_D, ABS_S, ABS_D, MOV_S, MOV_D, NEG_S, NEG_D, ROUND_W_S, ROUND_W_D, TRUNC_W_S,
+        TRUNC_W_D, CEIL_W_S, CEIL_W_D, FLOOR_W_S, FLOOR_W_D, MOVF_S, MOVF_D, MOVT_S, MOVT_D,
+        MOVZ_S, MOVZ_D, MOVN_S, MOVN_D, CVT_S_D, CVT_S_W, CVT_D_S, CVT_D_W, CVT_W_S, CVT_W_D,
+        C_EQ_S, C_EQ_D, C_LT_S, C_LT_D, C_LE_S, C_LE_D, MFC1, MTC1, MOVF, MOVT, BC1F, BC1T, LWC1,
+        SWC1, LDC1, SDC1,
     ];
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::vm::{MemoryLayout, VM};
+
+    fn decode(word: u32) -> InstructionData<'static> {
+        VM::new(MemoryLayout::mars(0x1000, 0x1000))
+            .decode_instruction(word)
+            .unwrap()
+    }
+
+    #[test]
+    fn only_sll_with_zero_fields_is_null() {
+        assert!(decode(0x00000000).is_null());
+        assert!(!decode(0x00000040).is_null());
+        // madd $0, $0 and ext $0, $0, 0, 1 are all zero outside their opcodes.
+        assert!(!decode(0x70000000).is_null());
+        assert!(!decode(0x7c000000).is_null());
+    }
+
+    #[test]
+    fn cop1_moves_to_zero_are_not_null() {
+        // mfc1 $zero, $f0
+        assert!(!decode(0x44000000).is_null());
+    }
+}
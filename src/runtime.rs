@@ -1,7 +1,21 @@
-pub mod vm;
-pub mod memory;
-pub mod logging;
+pub mod breakpoints;
+pub mod coverage;
+pub mod cp0;
+pub mod debug_info;
+mod decoding;
+pub mod errors;
 pub mod execution;
+pub mod expression;
+pub mod fpu;
+pub mod journal;
+pub mod loader;
+pub mod logging;
+pub mod memory;
+pub mod profiler;
 pub mod register_aliases;
-pub mod errors;
-mod decoding;
\ No newline at end of file
+pub mod statistics;
+pub mod stepping;
+pub mod syscalls;
+pub mod trace;
+pub mod vm;
+pub mod watchpoints;
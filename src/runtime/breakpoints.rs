@@ -0,0 +1,199 @@
+use crate::runtime::expression::Expression;
+use crate::runtime::vm::VM;
+
+/// Where a breakpoint stops execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointLocation {
+    /// Before the instruction at the given address.
+    Address(usize),
+    /// Before the first instruction assembled from the given source line.
+    Line(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub location: BreakpointLocation,
+    /// Only stop when this evaluates to a non-zero value.
+    pub condition: Option<Expression>,
+    /// How many times execution has reached the breakpoint, whether or not it stopped.
+    pub hits: u64,
+}
+
+impl Breakpoint {
+    pub fn new(location: BreakpointLocation) -> Breakpoint {
+        Breakpoint {
+            location,
+            condition: None,
+            hits: 0,
+        }
+    }
+
+    pub fn with_condition(location: BreakpointLocation, condition: Expression) -> Breakpoint {
+        Breakpoint {
+            condition: Some(condition),
+            ..Breakpoint::new(location)
+        }
+    }
+}
+
+impl VM {
+    pub fn get_breakpoints(&self) -> &Vec<Breakpoint> {
+        &self.breakpoints
+    }
+
+    /// Add a breakpoint, unless there is already one at the same location.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if self.find_breakpoint(breakpoint.location).is_none() {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Remove the breakpoint at the given location. Return false if there was none.
+    pub fn remove_breakpoint(&mut self, location: BreakpointLocation) -> bool {
+        match self.find_breakpoint(location) {
+            Some(index) => {
+                self.breakpoints.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add a breakpoint at the given location, or remove the breakpoints that stop at the same
+    /// address if there are any, however they were given. Return true if the breakpoint was
+    /// added.
+    pub fn toggle_breakpoint(&mut self, location: BreakpointLocation) -> bool {
+        let before = self.breakpoints.len();
+
+        match self.resolve_breakpoint(location) {
+            Some(address) => {
+                self.breakpoints = std::mem::take(&mut self.breakpoints)
+                    .into_iter()
+                    .filter(|breakpoint| {
+                        self.resolve_breakpoint(breakpoint.location) != Some(address)
+                    })
+                    .collect();
+            }
+            None => {
+                self.remove_breakpoint(location);
+            }
+        }
+
+        if self.breakpoints.len() < before {
+            false
+        } else {
+            self.breakpoints.push(Breakpoint::new(location));
+            true
+        }
+    }
+
+    /// Return true if a breakpoint stops before the instruction at `address`.
+    pub fn has_breakpoint_at(&self, address: usize) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|breakpoint| self.resolve_breakpoint(breakpoint.location) == Some(address))
+    }
+
+    /// Return true if a breakpoint stops before the next instruction. Every breakpoint at the
+    /// next instruction counts a hit, and a condition that cannot be evaluated stops too.
+    pub fn at_breakpoint(&mut self) -> bool {
+        self.check_breakpoints(true)
+    }
+
+    /// Like `at_breakpoint`, but without counting hits, for when execution goes backwards.
+    pub fn at_breakpoint_without_hit(&mut self) -> bool {
+        self.check_breakpoints(false)
+    }
+
+    fn check_breakpoints(&mut self, count_hit: bool) -> bool {
+        let pc = self.get_pc();
+        let mut stop = false;
+
+        for index in 0..self.breakpoints.len() {
+            if self.resolve_breakpoint(self.breakpoints[index].location) != Some(pc) {
+                continue;
+            }
+
+            if count_hit {
+                self.breakpoints[index].hits += 1;
+            }
+
+            let breakpoint = &self.breakpoints[index];
+
+            stop |= match &breakpoint.condition {
+                Some(condition) => {
+                    !matches!(self.evaluate(condition, Some(breakpoint.hits)), Ok(0))
+                }
+                None => true,
+            };
+        }
+
+        stop
+    }
+
+    /// Get the locations of breakpoints that cannot stop, because they refer to a source line
+    /// that no instruction was assembled from.
+    pub fn get_unresolved_breakpoints(&self) -> Vec<BreakpointLocation> {
+        self.breakpoints
+            .iter()
+            .map(|breakpoint| breakpoint.location)
+            .filter(|location| self.resolve_breakpoint(*location).is_none())
+            .collect()
+    }
+
+    /// Get the address a breakpoint location stops at, if it refers to an instruction.
+    fn resolve_breakpoint(&self, location: BreakpointLocation) -> Option<usize> {
+        match location {
+            BreakpointLocation::Address(address) => Some(address),
+            BreakpointLocation::Line(line) => self.debug_info.address_of(line),
+        }
+    }
+
+    fn find_breakpoint(&self, location: BreakpointLocation) -> Option<usize> {
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.location == location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::vm::MemoryLayout;
+
+    #[test]
+    fn line_breakpoints_resolve_through_debug_info() {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400000)));
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Line(3)));
+
+        assert_eq!(
+            vm.get_unresolved_breakpoints(),
+            vec![BreakpointLocation::Line(3)]
+        );
+
+        vm.debug_info.add_line(0x00400004, 3);
+
+        assert!(vm.get_unresolved_breakpoints().is_empty());
+        assert!(vm.has_breakpoint_at(0x00400004));
+    }
+
+    #[test]
+    fn toggling_removes_breakpoints_at_the_same_address() {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        vm.debug_info.add_line(0x00400004, 3);
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Line(3)));
+
+        assert!(!vm.toggle_breakpoint(BreakpointLocation::Address(0x00400004)));
+        assert!(vm.get_breakpoints().is_empty());
+
+        assert!(vm.toggle_breakpoint(BreakpointLocation::Address(0x00400004)));
+        assert!(!vm.toggle_breakpoint(BreakpointLocation::Line(3)));
+        assert!(vm.get_breakpoints().is_empty());
+
+        // Unresolved line breakpoints are toggled by their line.
+        assert!(vm.toggle_breakpoint(BreakpointLocation::Line(9)));
+        assert!(!vm.toggle_breakpoint(BreakpointLocation::Line(9)));
+        assert!(vm.get_breakpoints().is_empty());
+    }
+}
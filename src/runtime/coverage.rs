@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::mips::instruction::InstructionData;
+use crate::runtime::statistics::InstructionCategory;
+use crate::runtime::vm::VM;
+
+/// How often a conditional branch went each way.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BranchOutcomes {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// Records which instructions of the program were executed, and which way each conditional
+/// branch went.
+#[derive(Clone)]
+pub struct Coverage {
+    /// The addresses of the program's instructions, whether executed or not.
+    addresses: Vec<usize>,
+    /// The number of times the instruction at each address was executed.
+    hits: HashMap<usize, u64>,
+    branches: HashMap<usize, BranchOutcomes>,
+}
+
+impl Coverage {
+    /// Start recording coverage of the instructions at the given addresses.
+    pub fn new(mut addresses: Vec<usize>) -> Coverage {
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        Coverage {
+            addresses,
+            hits: HashMap::new(),
+            branches: HashMap::new(),
+        }
+    }
+
+    /// A coverage record of the same program with nothing executed yet.
+    pub fn cleared(&self) -> Coverage {
+        Coverage::new(self.addresses.clone())
+    }
+
+    /// Count the instruction executed at `address`. `next` is the address execution continues
+    /// at, which tells whether a branch was taken.
+    pub fn record(&mut self, address: usize, instruction: &InstructionData, next: usize) {
+        *self.hits.entry(address).or_insert(0) += 1;
+
+        if is_branch(instruction) {
+            let outcomes = self.branches.entry(address).or_default();
+
+            if next == address + 4 {
+                outcomes.not_taken += 1;
+            } else {
+                outcomes.taken += 1;
+            }
+        }
+    }
+
+    pub fn get_hits(&self, address: usize) -> u64 {
+        self.hits.get(&address).copied().unwrap_or(0)
+    }
+
+    pub fn get_branch_outcomes(&self, address: usize) -> Option<BranchOutcomes> {
+        self.branches.get(&address).copied()
+    }
+
+    /// All addresses of the program, including any executed outside the ones it was
+    /// created with.
+    fn all_addresses(&self) -> Vec<usize> {
+        let mut addresses = self.addresses.clone();
+        addresses.extend(self.hits.keys());
+        addresses.sort_unstable();
+        addresses.dedup();
+        addresses
+    }
+
+    /// The addresses of the conditional branches in the program, executed or not.
+    fn branch_addresses(&self, vm: &VM) -> Vec<usize> {
+        self.all_addresses()
+            .into_iter()
+            .filter(|address| {
+                self.branches.contains_key(address)
+                    || vm
+                        .memory
+                        .get_word(*address)
+                        .and_then(|word| vm.decode_instruction(word))
+                        .is_ok_and(|instruction| is_branch(&instruction))
+            })
+            .collect()
+    }
+
+    /// Coverage in the lcov tracefile format, as read by genhtml and most CI services.
+    /// Source lines come from the debug information of `vm`, and `source_file` is the path
+    /// given for them. Each label in the text segment is reported as a function.
+    ///
+    /// Fail if the program has no line information, as the report would be empty.
+    pub fn lcov(&self, vm: &VM, source_file: &str) -> Result<String, String> {
+        let debug_info = &vm.debug_info;
+        let mut lcov = String::new();
+
+        if debug_info.instruction_addresses().next().is_none() {
+            return Err(String::from(
+                "Line coverage needs line information from --symbols",
+            ));
+        }
+
+        writeln!(lcov, "TN:").unwrap();
+        writeln!(lcov, "SF:{}", source_file).unwrap();
+
+        let addresses = self.all_addresses();
+        let in_program = |address: &usize| addresses.binary_search(address).is_ok();
+
+        let functions: Vec<_> = debug_info
+            .get_symbols()
+            .iter()
+            .filter(|(_, address)| in_program(address))
+            .filter_map(|(name, address)| Some((name, debug_info.line_of(*address)?, *address)))
+            .collect();
+
+        for (name, line, _) in &functions {
+            writeln!(lcov, "FN:{},{}", line, name).unwrap();
+        }
+        for (name, _, address) in &functions {
+            writeln!(lcov, "FNDA:{},{}", self.get_hits(*address), name).unwrap();
+        }
+        writeln!(lcov, "FNF:{}", functions.len()).unwrap();
+        writeln!(
+            lcov,
+            "FNH:{}",
+            functions
+                .iter()
+                .filter(|(_, _, address)| self.get_hits(*address) > 0)
+                .count()
+        )
+        .unwrap();
+
+        let branches: Vec<_> = self
+            .branch_addresses(vm)
+            .into_iter()
+            .filter_map(|address| Some((address, debug_info.line_of(address)?)))
+            .collect();
+        let mut branches_hit = 0;
+
+        for (block, (address, line)) in branches.iter().enumerate() {
+            // lcov uses "-" for the branches of a block that never executed.
+            let (taken, not_taken) = match self.get_branch_outcomes(*address) {
+                Some(outcomes) => {
+                    branches_hit += (outcomes.taken > 0) as usize;
+                    branches_hit += (outcomes.not_taken > 0) as usize;
+                    (outcomes.taken.to_string(), outcomes.not_taken.to_string())
+                }
+                None => (String::from("-"), String::from("-")),
+            };
+
+            writeln!(lcov, "BRDA:{},{},0,{}", line, block, taken).unwrap();
+            writeln!(lcov, "BRDA:{},{},1,{}", line, block, not_taken).unwrap();
+        }
+        writeln!(lcov, "BRF:{}", branches.len() * 2).unwrap();
+        writeln!(lcov, "BRH:{}", branches_hit).unwrap();
+
+        // A line that assembles to several instructions counts as executed as many times as
+        // its most executed instruction.
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+
+        for address in &addresses {
+            if let Some(line) = debug_info.line_of(*address) {
+                let hits = lines.entry(line).or_insert(0);
+                *hits = (*hits).max(self.get_hits(*address));
+            }
+        }
+
+        for (line, hits) in &lines {
+            writeln!(lcov, "DA:{},{}", line, hits).unwrap();
+        }
+        writeln!(lcov, "LF:{}", lines.len()).unwrap();
+        writeln!(
+            lcov,
+            "LH:{}",
+            lines.values().filter(|hits| **hits > 0).count()
+        )
+        .unwrap();
+
+        writeln!(lcov, "end_of_record").unwrap();
+
+        Ok(lcov)
+    }
+
+    /// A table of the instructions and branch outcomes covered under each label, in address
+    /// order, with a total at the end.
+    pub fn summary(&self, vm: &VM) -> String {
+        let debug_info = &vm.debug_info;
+        let branches = self.branch_addresses(vm);
+
+        // Executed and total instructions, then covered and total branch outcomes.
+        let mut labels: Vec<(String, [usize; 4])> = vec![];
+        let mut total = [0; 4];
+
+        for address in self.all_addresses() {
+            let label = debug_info
+                .enclosing_symbol(address)
+                .map_or("(no label)", |(label, _)| label);
+
+            if labels.last().map(|(name, _)| name.as_str()) != Some(label) {
+                labels.push((label.to_owned(), [0; 4]));
+            }
+
+            let counts = &mut labels.last_mut().unwrap().1;
+            let mut add = |index: usize, count: usize| {
+                counts[index] += count;
+                total[index] += count;
+            };
+
+            add(0, (self.get_hits(address) > 0) as usize);
+            add(1, 1);
+
+            if branches.contains(&address) {
+                let outcomes = self.get_branch_outcomes(address).unwrap_or_default();
+                add(
+                    2,
+                    (outcomes.taken > 0) as usize + (outcomes.not_taken > 0) as usize,
+                );
+                add(3, 2);
+            }
+        }
+
+        let mut summary = String::new();
+
+        writeln!(
+            summary,
+            "{:<24} {:>21} {:>21}",
+            "Label", "Instructions", "Branches"
+        )
+        .unwrap();
+
+        for (label, counts) in labels
+            .iter()
+            .map(|(label, counts)| (label.as_str(), counts))
+            .chain(std::iter::once(("Total", &total)))
+        {
+            writeln!(
+                summary,
+                "{:<24} {:>21} {:>21}",
+                label,
+                fraction(counts[0], counts[1]),
+                fraction(counts[2], counts[3])
+            )
+            .unwrap();
+        }
+
+        summary
+    }
+}
+
+fn is_branch(instruction: &InstructionData) -> bool {
+    InstructionCategory::of(instruction.base.name) == InstructionCategory::Branch
+}
+
+/// Format a count out of a total with its percentage, such as "3/4 (75.0%)", or "-" if the
+/// total is zero.
+fn fraction(count: usize, total: usize) -> String {
+    if total == 0 {
+        String::from("-")
+    } else {
+        format!(
+            "{}/{} ({:.1}%)",
+            count,
+            total,
+            count as f64 * 100.0 / total as f64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::debug_info::DebugInfo;
+    use crate::runtime::loader::load_words;
+    use crate::runtime::stepping::{Run, RunTarget};
+    use crate::runtime::vm::MemoryLayout;
+
+    /// A VM that has run `li $t0, 1; beq $t0, $zero, 8; li $t1, 2` to the null instruction
+    /// after it, with coverage of those three instructions.
+    fn covered_vm(debug_info: DebugInfo) -> VM {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        vm.debug_info = debug_info;
+        load_words(&mut vm, &[0x24080001, 0x11000001, 0x24090002, 0]);
+        vm.coverage = Some(Coverage::new(vec![0x00400000, 0x00400004, 0x00400008]));
+        vm.run(&mut Run::new(RunTarget::Continue), 10).unwrap();
+        vm
+    }
+
+    #[test]
+    fn lcov_reports_lines_functions_and_branches() {
+        let debug_info = DebugInfo::parse(
+            "symbol main 0x00400000\nline 1 0x00400000\nline 2 0x00400004\nline 3 0x00400008",
+        )
+        .unwrap();
+        let vm = covered_vm(debug_info);
+        let lcov = vm.coverage.as_ref().unwrap().lcov(&vm, "main.s").unwrap();
+
+        for record in [
+            "SF:main.s",
+            "FN:1,main",
+            "FNDA:1,main",
+            "BRDA:2,0,0,0",
+            "BRDA:2,0,1,1",
+            "BRH:1",
+            "DA:3,1",
+            "LF:3",
+            "LH:3",
+        ] {
+            assert!(lcov.lines().any(|line| line == record), "no {}", record);
+        }
+    }
+
+    #[test]
+    fn lcov_needs_line_information() {
+        let vm = covered_vm(DebugInfo::new());
+        let coverage = vm.coverage.as_ref().unwrap();
+
+        assert!(coverage.lcov(&vm, "main.s").is_err());
+        assert!(coverage.summary(&vm).contains("3/3 (100.0%)"));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::runtime::errors::ExceptionCode;
+
+/// Coprocessor 0, which records the cause and location of exceptions.
+///
+/// Only the registers used by MARS are modelled.
+#[derive(Clone)]
+pub struct Coprocessor0 {
+    /// The address that caused the most recent address error.
+    bad_vaddr: u32,
+    status: u32,
+    cause: u32,
+    /// The address of the instruction that caused the most recent exception.
+    epc: u32,
+}
+
+impl Coprocessor0 {
+    /// The register numbers and names of the modelled registers.
+    pub const REGISTERS: [(u8, &'static str); 4] =
+        [(8, "vaddr"), (12, "status"), (13, "cause"), (14, "epc")];
+
+    pub fn new() -> Coprocessor0 {
+        Coprocessor0 {
+            bad_vaddr: 0,
+            // User mode, with interrupts enabled and unmasked, as in MARS.
+            status: 0x0000ff11,
+            cause: 0,
+            epc: 0,
+        }
+    }
+
+    /// Get the value of the given register, or None if it is not modelled.
+    pub fn get_register(&self, register: u8) -> Option<u32> {
+        match register {
+            8 => Some(self.bad_vaddr),
+            12 => Some(self.status),
+            13 => Some(self.cause),
+            14 => Some(self.epc),
+            _ => None,
+        }
+    }
+
+    /// Record an exception raised by the instruction at `pc`. If the instruction was in a
+    /// branch delay slot, `pc` is the address of the branch.
+    pub fn record_exception(&mut self, code: ExceptionCode, pc: u32, delay_slot: bool) {
+        // The exception code occupies bits 2 to 6 of the Cause register, and bit 31 marks
+        // an exception in a delay slot.
+        self.cause =
+            (self.cause & !0x8000007c) | ((code as u32) << 2) | ((delay_slot as u32) << 31);
+        self.epc = pc;
+        // Set the exception level bit.
+        self.status |= 0b10;
+    }
+
+    /// Record an address error caused by accessing `address`.
+    pub fn record_address_error(
+        &mut self,
+        code: ExceptionCode,
+        pc: u32,
+        delay_slot: bool,
+        address: u32,
+    ) {
+        self.bad_vaddr = address;
+        self.record_exception(code, pc, delay_slot);
+    }
+}
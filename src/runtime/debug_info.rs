@@ -0,0 +1,193 @@
+use crate::runtime::memory::parse_address;
+
+/// Source information for a loaded program, as produced by the assembler or read from a
+/// symbol file. Programs loaded from raw binaries have none unless given a symbol file.
+#[derive(Clone)]
+pub struct DebugInfo {
+    /// The source line of each instruction, as (address, line) pairs sorted by address.
+    lines: Vec<(usize, usize)>,
+    /// Labels and their addresses.
+    symbols: Vec<(String, usize)>,
+    /// The source file, one entry per line. Line numbers start at 1.
+    source: Vec<String>,
+    /// The path of the source file, if known.
+    source_path: Option<String>,
+}
+
+impl DebugInfo {
+    pub fn new() -> DebugInfo {
+        DebugInfo {
+            lines: vec![],
+            symbols: vec![],
+            source: vec![],
+            source_path: None,
+        }
+    }
+
+    /// Parse a symbol file, which gives the debug information of a raw binary. Each line is
+    /// one of:
+    ///
+    /// ```text
+    /// symbol <name> <address>
+    /// line <line> <address>
+    /// source <path>
+    /// ```
+    ///
+    /// Blank lines and lines starting with `#` are ignored. The source file is not read.
+    pub fn parse(text: &str) -> Result<DebugInfo, String> {
+        let mut debug_info = DebugInfo::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            let result = match words[..] {
+                [] => Ok(()),
+                [first, ..] if first.starts_with('#') => Ok(()),
+                ["symbol", name, address] => {
+                    parse_address(address).map(|address| debug_info.add_symbol(name, address))
+                }
+                ["line", number, address] => number
+                    .parse()
+                    .map_err(|_| format!("Invalid line number \"{}\"", number))
+                    .and_then(|number| {
+                        parse_address(address).map(|address| debug_info.add_line(address, number))
+                    }),
+                ["source", path] => {
+                    debug_info.source_path = Some(path.to_owned());
+                    Ok(())
+                }
+                _ => Err(format!("Unexpected \"{}\"", line.trim())),
+            };
+
+            result.map_err(|err| format!("Line {}: {}", index + 1, err))?;
+        }
+
+        Ok(debug_info)
+    }
+
+    pub fn add_symbol(&mut self, name: &str, address: usize) {
+        self.symbols.push((name.to_owned(), address));
+    }
+
+    /// Get the address of the given label, if it is defined.
+    pub fn symbol_address(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, address)| *address)
+    }
+
+    pub fn get_symbols(&self) -> &Vec<(String, usize)> {
+        &self.symbols
+    }
+
+    /// Get the labels defined at `address`.
+    pub fn symbols_at(&self, address: usize) -> impl Iterator<Item = &str> {
+        self.symbols
+            .iter()
+            .filter(move |(_, a)| *a == address)
+            .map(|(symbol, _)| symbol.as_str())
+    }
+
+    /// Get the closest label at or before `address`, and its address.
+    pub fn enclosing_symbol(&self, address: usize) -> Option<(&str, usize)> {
+        self.symbols
+            .iter()
+            .filter(|(_, a)| *a <= address)
+            .max_by_key(|(_, a)| *a)
+            .map(|(symbol, a)| (symbol.as_str(), *a))
+    }
+
+    pub fn set_source(&mut self, text: &str) {
+        self.source = text.lines().map(str::to_owned).collect();
+    }
+
+    pub fn get_source_path(&self) -> Option<&str> {
+        self.source_path.as_deref()
+    }
+
+    pub fn set_source_path(&mut self, path: &str) {
+        self.source_path = Some(path.to_owned());
+    }
+
+    pub fn get_source(&self) -> &Vec<String> {
+        &self.source
+    }
+
+    /// Get the text of the given line of the source, if it exists.
+    pub fn source_line(&self, line: usize) -> Option<&str> {
+        line.checked_sub(1)
+            .and_then(|index| self.source.get(index))
+            .map(String::as_str)
+    }
+
+    /// Record that the instruction at `address` was assembled from the given line.
+    pub fn add_line(&mut self, address: usize, line: usize) {
+        let index = self.lines.partition_point(|(a, _)| *a < address);
+
+        if self.lines.get(index).map(|(a, _)| *a) == Some(address) {
+            self.lines[index].1 = line;
+        } else {
+            self.lines.insert(index, (address, line));
+        }
+    }
+
+    /// Get the addresses of all instructions with a known source line.
+    pub fn instruction_addresses(&self) -> impl Iterator<Item = usize> + '_ {
+        self.lines.iter().map(|(address, _)| *address)
+    }
+
+    /// Get the source line of the instruction at `address`, if known.
+    pub fn line_of(&self, address: usize) -> Option<usize> {
+        self.lines
+            .binary_search_by_key(&address, |(a, _)| *a)
+            .ok()
+            .map(|index| self.lines[index].1)
+    }
+
+    /// Get the address of the first instruction assembled from the given line, if any.
+    pub fn address_of(&self, line: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .find(|(_, l)| *l == line)
+            .map(|(address, _)| *address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_symbol_file() {
+        let debug_info = DebugInfo::parse(
+            "# Built from loop.s\n\
+             source loop.s\n\
+             \n\
+             symbol main 0x00400000\n\
+             symbol loop 4194312\n\
+             line 3 0x00400000\n\
+             line 5 0x00400008\n\
+             line 4 0x00400004\n",
+        )
+        .unwrap();
+
+        assert_eq!(debug_info.get_source_path(), Some("loop.s"));
+        assert_eq!(debug_info.symbol_address("main"), Some(0x00400000));
+        assert_eq!(debug_info.symbol_address("loop"), Some(0x00400008));
+        assert_eq!(debug_info.line_of(0x00400004), Some(4));
+        assert_eq!(debug_info.address_of(5), Some(0x00400008));
+    }
+
+    #[test]
+    fn parse_errors_give_the_line() {
+        for (text, message) in [
+            ("symbol main", "Line 1: Unexpected \"symbol main\""),
+            ("\nline x 0x0", "Line 2: Invalid line number \"x\""),
+            ("symbol main 0xg", "Line 1: Invalid address \"0xg\""),
+            ("label main 0x0", "Line 1: Unexpected \"label main 0x0\""),
+        ] {
+            assert_eq!(DebugInfo::parse(text).err().as_deref(), Some(message));
+        }
+    }
+}
@@ -1,12 +1,16 @@
 use crate::mips::instruction::instructions::*;
 use crate::mips::instruction::{
-    self, Instruction, InstructionArgs, InstructionData, InstructionFormat,
+    self, Instruction, InstructionArgs, InstructionData, InstructionFormat, SPECIAL, SPECIAL2,
+    SPECIAL3,
 };
 use crate::runtime::errors::{FatalErrorType, RuntimeError};
 use crate::runtime::vm;
 
 impl vm::VM {
-    pub fn decode_instruction(&self, instruction: u32) -> Result<InstructionData, RuntimeError> {
+    pub fn decode_instruction(
+        &self,
+        instruction: u32,
+    ) -> Result<InstructionData<'static>, RuntimeError> {
         let base_instruction = self.decode_base_instruction(instruction)?;
 
         Ok(InstructionData {
@@ -43,45 +47,166 @@ impl vm::VM {
         })
     }
 
-    fn decode_base_instruction(&self, instruction: u32) -> Result<&Instruction, RuntimeError> {
-        let b1 = (instruction >> 24) as u8;
+    fn decode_base_instruction(
+        &self,
+        instruction: u32,
+    ) -> Result<&'static Instruction<'static>, RuntimeError> {
+        let opcode = (instruction >> 26) as u8;
 
-        let opcode: u8 = b1 >> 2;
+        let inst = match ALL_INSTRUCTIONS
+            .iter()
+            .find(|inst| inst.matches(instruction))
+        {
+            Some(inst) => inst,
+            None if opcode == SPECIAL || opcode == SPECIAL2 || opcode == SPECIAL3 => {
+                // R-type
+                let func_code = (instruction & 0b111111) as u8;
 
-        if opcode == 0 {
-            // R-type
-
-            let func_code: u8 = ((instruction << 26) >> 26) as u8;
-
-            for inst in ALL_INSTRUCTIONS.iter() {
-                if inst.opc_func == func_code {
-                    return Ok(inst);
-                }
+                return Err(RuntimeError::new(
+                    FatalErrorType::IllegalInstruction,
+                    format!(
+                        "Unknown R-type instruction: {:#010x} (function code {:#08b})",
+                        instruction, func_code
+                    ),
+                ));
+            }
+            None => {
+                return Err(RuntimeError::new(
+                    FatalErrorType::IllegalInstruction,
+                    format!(
+                        "Unknown instruction: {:#010x} (opcode {:#08b})",
+                        instruction, opcode
+                    ),
+                ));
             }
+        };
 
+        if inst.isa > self.options.isa {
             return Err(RuntimeError::new(
                 FatalErrorType::IllegalInstruction,
                 format!(
-                    "Unknown R-type instruction: {:#010x} (function code {:#08b})",
-                    instruction, func_code
+                    "Instruction \"{}\" ({:#010x}) requires {}, but the VM is restricted to {}",
+                    inst.name,
+                    instruction,
+                    inst.isa.name(),
+                    self.options.isa.name()
                 ),
             ));
-        } else {
-            // I-type
+        }
 
-            for inst in ALL_INSTRUCTIONS.iter() {
-                if inst.opc_func == opcode {
-                    return Ok(inst);
-                }
-            }
+        Ok(inst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mips::disassembler::disassemble;
+    use crate::mips::instruction::Isa;
+    use crate::runtime::debug_info::DebugInfo;
+    use crate::runtime::vm::{MemoryLayout, VM};
+
+    fn disassembled(vm: &VM, word: u32) -> String {
+        let instruction = vm.decode_instruction(word).unwrap();
+        disassemble(&instruction, 0x00400000, |_| None).to_string()
+    }
+
+    fn assert_decodes(words: &[(u32, &str)]) {
+        let vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+
+        for (word, text) in words {
+            assert_eq!(disassembled(&vm, *word), *text, "decoding {:#010x}", word);
         }
+    }
+
+    #[test]
+    fn decodes_special2() {
+        assert_decodes(&[
+            (0x712a4002, "mul $t0, $t1, $t2"),
+            (0x712a0000, "madd $t1, $t2"),
+            (0x712a0005, "msubu $t1, $t2"),
+            (0x71284020, "clz $t0, $t1"),
+        ]);
+    }
+
+    #[test]
+    fn decodes_special3() {
+        assert_decodes(&[
+            (0x7d283900, "ext $t0, $t1, 4, 8"),
+            (0x7d285904, "ins $t0, $t1, 4, 8"),
+            (0x7c094420, "seb $t0, $t1"),
+            (0x7c094620, "seh $t0, $t1"),
+            (0x7c0940a0, "wsbh $t0, $t1"),
+        ]);
+    }
+
+    #[test]
+    fn tells_rotates_from_shifts() {
+        assert_decodes(&[
+            (0x00094102, "srl $t0, $t1, 4"),
+            (0x00294102, "rotr $t0, $t1, 4"),
+            (0x01494006, "srlv $t0, $t1, $t2"),
+            (0x01494046, "rotrv $t0, $t1, $t2"),
+            (0x012a400b, "movn $t0, $t1, $t2"),
+        ]);
+    }
+
+    #[test]
+    fn decodes_unaligned_and_atomic_memory_instructions() {
+        assert_decodes(&[
+            (0x89280001, "lwl $t0, 1($t1)"),
+            (0x99280004, "lwr $t0, 4($t1)"),
+            (0xa928fffd, "swl $t0, -3($t1)"),
+            (0xb9280000, "swr $t0, 0($t1)"),
+            (0xc1280000, "ll $t0, 0($t1)"),
+            (0xe1280000, "sc $t0, 0($t1)"),
+        ]);
+    }
+
+    #[test]
+    fn decodes_cop1() {
+        assert_decodes(&[
+            (0x46041000, "add.s $f0, $f2, $f4"),
+            (0x46221102, "mul.d $f4, $f2, $f2"),
+            (0x460001a4, "cvt.w.s $f6, $f0"),
+            (0x46801020, "cvt.s.w $f0, $f2"),
+            (0x4604103c, "c.lt.s $f2, $f4"),
+            (0x44080800, "mfc1 $t0, $f1"),
+            (0x44880800, "mtc1 $t0, $f1"),
+            (0x45010003, "bc1t 0x00400010"),
+            (0xc5200004, "lwc1 $f0, 4($t1)"),
+            (0xd5220000, "ldc1 $f2, 0($t1)"),
+        ]);
+    }
+
+    #[test]
+    fn restricting_the_isa_rejects_later_instructions() {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        vm.options.isa = Isa::MipsI;
+
+        assert_eq!(disassembled(&vm, 0x00094102), "srl $t0, $t1, 4");
+        assert!(vm.decode_instruction(0x712a4002).is_err());
+        assert!(vm.decode_instruction(0x00294102).is_err());
+
+        vm.options.isa = Isa::Mips32;
+        assert_eq!(disassembled(&vm, 0x712a4002), "mul $t0, $t1, $t2");
+        assert!(vm.decode_instruction(0x7d283900).is_err());
+    }
+
+    #[test]
+    fn targets_are_shown_as_symbols() {
+        let vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        let debug_info =
+            DebugInfo::parse("symbol loop 0x0040000c\nsymbol exit 0x00400008").unwrap();
+        let disassembled = |word| {
+            let instruction = vm.decode_instruction(word).unwrap();
+            disassemble(&instruction, 0x00400000, |target| {
+                debug_info.symbols_at(target as usize).next()
+            })
+            .to_string()
+        };
 
-        Err(RuntimeError::new(
-            FatalErrorType::IllegalInstruction,
-            format!(
-                "Unknown instruction: {:#010x} (opcode {:#08b})",
-                instruction, opcode
-            ),
-        ))
+        assert_eq!(disassembled(0x11000002), "beq $t0, $zero, loop");
+        assert_eq!(disassembled(0x08100002), "j exit");
+        assert_eq!(disassembled(0x08100004), "j 0x00400010");
     }
 }
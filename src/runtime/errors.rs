@@ -3,6 +3,7 @@ pub enum FatalErrorType {
     IllegalMemoryAccess,
     IllegalInstruction,
     IllegalRegisterAccess,
+    InvalidSyscall,
 }
 
 #[derive(Debug)]
@@ -31,13 +32,38 @@ impl RuntimeError {
     }
 }
 
+/// Exception codes, as recorded in the Cause register of coprocessor 0.
+#[derive(Debug, Clone, Copy)]
+pub enum ExceptionCode {
+    AddressErrorLoad = 4,
+    AddressErrorStore = 5,
+    Syscall = 8,
+    Breakpoint = 9,
+    ReservedInstruction = 10,
+    Overflow = 12,
+    Trap = 13,
+}
+
 /// A trap is a non-fatal error that can be handled by the program.
 pub struct Trap {
+    pub code: ExceptionCode,
     pub message: String,
 }
 
 impl Trap {
-    pub fn new(message: String) -> Trap {
-        Trap { message }
+    pub fn new(code: ExceptionCode, message: String) -> Trap {
+        Trap { code, message }
     }
-}
\ No newline at end of file
+}
+
+/// A warning about behaviour that is legal but probably a mistake, such as a load hazard.
+/// Warnings do not stop execution.
+pub struct Warning {
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(message: String) -> Warning {
+        Warning { message }
+    }
+}
@@ -1,6 +1,8 @@
-use crate::mips::instruction::{coerce_i_format, coerece_r_format, InstructionData};
-use crate::runtime::errors::{FatalErrorType, RuntimeError, Trap};
-use crate::runtime::vm::VM;
+use crate::mips::instruction::{
+    coerce_i_format, coerce_j_format, coerece_r_format, InstructionData,
+};
+use crate::runtime::errors::{ExceptionCode, FatalErrorType, RuntimeError, Trap};
+use crate::runtime::vm::{DelayedLoad, VM};
 
 #[derive(Debug, Copy, Clone)]
 enum ShiftDirection {
@@ -12,6 +14,58 @@ enum ShiftDirection {
 enum ShiftType {
     Logical,
     Arithmetic,
+    /// Bits shifted out of one end are shifted back in at the other.
+    Rotate,
+}
+
+/// How a floating point or integer value is stored in floating point registers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FloatFormat {
+    Single,
+    Double,
+    /// A 32-bit two's complement integer.
+    Word,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum FloatOperation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum FloatUnaryOperation {
+    Sqrt,
+    Abs,
+    Neg,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum FloatCondition {
+    Equal,
+    LessThan,
+    LessOrEqual,
+}
+
+/// How to round a floating point value when converting it to an integer.
+#[derive(Debug, Copy, Clone)]
+enum RoundingMode {
+    /// Round to the nearest integer, with ties going to the even integer.
+    Nearest,
+    Zero,
+    Up,
+    Down,
+}
+
+/// How a branch compares its two operands. Ordered comparisons are signed.
+#[derive(Debug, Copy, Clone)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    LessOrEqual,
+    GreaterThan,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -52,13 +106,63 @@ enum ExecutionTask {
     Div {
         a: Target,
         b: Target,
-        overflow: bool,
+        signed: bool,
     },
     Mult {
         a: Target,
         b: Target,
         signed: bool,
     },
+    /// Multiply into a general purpose register. As in MARS, HI and LO are also set.
+    Mul {
+        dest: Target,
+        a: Target,
+        b: Target,
+    },
+    /// Add the product of `a` and `b` to HI and LO, or subtract it if `subtract` is set.
+    MultAccumulate {
+        a: Target,
+        b: Target,
+        signed: bool,
+        subtract: bool,
+    },
+    SetLessThan {
+        dest: Target,
+        a: Target,
+        b: Target,
+        signed: bool,
+    },
+    /// Count the leading zeros of `a`, or the leading ones if `ones` is set.
+    CountLeading {
+        dest: Target,
+        a: Target,
+        ones: bool,
+    },
+    /// Sign-extend the lowest `size` bytes of `a`.
+    SignExtend {
+        dest: Target,
+        a: Target,
+        size: u8,
+    },
+    /// Swap the bytes within each halfword of `a`.
+    SwapBytes {
+        dest: Target,
+        a: Target,
+    },
+    /// Extract `size` bits of `a` starting at bit `pos`.
+    ExtractBits {
+        dest: Target,
+        a: Target,
+        pos: u8,
+        size: u8,
+    },
+    /// Replace `size` bits of `dest` starting at bit `pos` with the lowest bits of `a`.
+    InsertBits {
+        dest: Target,
+        a: Target,
+        pos: u8,
+        size: u8,
+    },
     Shift {
         dest: Target,
         a: Target,
@@ -67,6 +171,19 @@ enum ExecutionTask {
         shift_type: ShiftType,
     },
 
+    /* Data movement */
+    Move {
+        dest: Target,
+        src: Target,
+    },
+    /// Move `src` to `dest` if `condition` is zero (when `if_zero` is set) or non-zero.
+    ConditionalMove {
+        dest: Target,
+        src: Target,
+        condition: Target,
+        if_zero: bool,
+    },
+
     /* Memory operations */
     Load {
         dest: Target,
@@ -83,9 +200,102 @@ enum ExecutionTask {
         size: u8,
     },
 
+    /// Load part of an unaligned word into the left (`lwl`) or right (`lwr`) of `dest`.
+    LoadPartial {
+        dest: Target,
+        src_addr: Target,
+        offset: Target,
+        left: bool,
+    },
+
+    /// Store the left (`swl`) or right (`swr`) part of `src` to an unaligned word.
+    StorePartial {
+        dest_addr: Target,
+        src: Target,
+        offset: Target,
+        left: bool,
+    },
+
+    /// Load a word and set the link bit for its address.
+    LoadLinked {
+        dest: Target,
+        src_addr: Target,
+        offset: Target,
+    },
+
+    /// Store `src` only if the link bit is still set, then write 1 to `src` if the store
+    /// happened and 0 otherwise.
+    StoreConditional {
+        dest_addr: Target,
+        src: Target,
+        offset: Target,
+    },
+
+    /// Load a doubleword into a pair of floating point registers.
+    LoadDouble {
+        dest: u8,
+        src_addr: Target,
+        offset: Target,
+    },
+
+    /// Store a pair of floating point registers as a doubleword.
+    StoreDouble {
+        dest_addr: Target,
+        src: u8,
+        offset: Target,
+    },
+
+    /* Floating point operations */
+    FloatBinary {
+        op: FloatOperation,
+        format: FloatFormat,
+        dest: u8,
+        a: u8,
+        b: u8,
+    },
+    FloatUnary {
+        op: FloatUnaryOperation,
+        format: FloatFormat,
+        dest: u8,
+        src: u8,
+    },
+    /// Move a floating point value, optionally only if `condition` is zero (when `if_zero` is
+    /// set) or non-zero.
+    FloatMove {
+        format: FloatFormat,
+        dest: u8,
+        src: u8,
+        condition: Option<(Target, bool)>,
+    },
+    FloatConvert {
+        from: FloatFormat,
+        to: FloatFormat,
+        dest: u8,
+        src: u8,
+        rounding: RoundingMode,
+    },
+    /// Compare two floating point values, and set the given condition flag to the result.
+    FloatCompare {
+        condition: FloatCondition,
+        format: FloatFormat,
+        a: u8,
+        b: u8,
+        flag: u8,
+    },
+
     /* Control flow operations */
+    /// Jump to the address in `dest`, saving the return address in `link` if given.
     Jump {
         dest: Target,
+        link: Option<u8>,
+    },
+    /// Branch by `offset` instructions, relative to the next instruction, if `a` and `b`
+    /// satisfy the comparison.
+    Branch {
+        a: Target,
+        b: Target,
+        comparison: Comparison,
+        offset: Target,
     },
 
     /* Other */
@@ -96,12 +306,20 @@ enum ExecutionTask {
 
 /// Represents a target for an instruction.
 ///
-/// This can be a register, memory, or immediate value.
+/// This can be a register, memory, or immediate value, or one of the HI and LO registers.
+/// Floating point registers and condition flags are accessed as raw 32-bit values.
 #[derive(Debug, Copy, Clone)]
 enum Target {
     Register(u8),
     Memory(u32),
     Immediate(u16, HalfWordExtension),
+    Hi,
+    Lo,
+    FloatRegister(u8),
+    /// A floating point condition flag, read as 1 if set and 0 if clear.
+    ConditionFlag(u8),
+    /// A full 32-bit address, such as the destination of a jump.
+    Address(u32),
 }
 
 /// Represents whether an immediate value should be sign-extended or zero-extended
@@ -117,38 +335,89 @@ impl VM {
     /// that was triggered, if any.
     pub fn run_single_instruction(
         &mut self,
-    ) -> Result<(InstructionData, Option<Trap>), RuntimeError> {
-        let instruction = self.fetch_instruction_code()?;
-        self.execute_instruction(instruction)
+    ) -> Result<(InstructionData<'static>, Option<Trap>), RuntimeError> {
+        self.begin_journal_step();
+        self.memory.clear_last_writes();
+        self.clear_last_changes();
+
+        let result = self.fetch_instruction_code().and_then(|instruction| {
+            // Watchpoints are armed after the fetch, so that only the instruction's own
+            // accesses trigger them.
+            self.memory.watchpoints.arm();
+            let result = self
+                .execute_instruction(instruction)
+                .map(|(_, trap)| (instruction, trap));
+            self.memory.watchpoints.disarm();
+
+            result
+        });
+
+        // Close the journal step even if the instruction failed, so that any changes it made
+        // before failing can still be undone.
+        self.end_journal_step();
+
+        let (instruction, trap) = result?;
+        Ok((self.decode_instruction(instruction)?, trap))
     }
 
     /// Fetch the next instruction from memory, and increment the program counter.
-    /// 
+    ///
     /// Return the four byte instruction code.
     pub fn fetch_instruction_code(&mut self) -> Result<u32, RuntimeError> {
         let pc = self.get_pc();
-        let instruction = self.memory.get_word(pc as usize)?;
+        let instruction = self.memory.get_word(pc)?;
         self.set_pc(pc + 4);
 
         Ok(instruction)
     }
 
     /// Execute the instruction given by the four byte instruction code.
-    /// 
+    ///
     /// Return the decoded instruction that was executed, and any trap that was triggered,
     /// if any.
     pub fn execute_instruction(
         &mut self,
         instruction: u32,
-    ) -> Result<(InstructionData, Option<Trap>), RuntimeError> {
+    ) -> Result<(InstructionData<'static>, Option<Trap>), RuntimeError> {
+        // If the previous instruction was a taken branch, this one is in its delay slot.
+        let delayed_branch = self.take_delayed_branch();
+        let delayed_load = self.take_delayed_load();
+        self.set_load_delay_slot(delayed_load);
+
         let inst = self.decode_instruction(instruction)?;
         let task = self.get_task(&inst)?;
         let mut trap: Option<Trap> = None;
 
         if !inst.is_null() {
-            if let Ok(t) = self.execute_task(task) {
-                trap = t;
+            trap = self.execute_task(task)?;
+        }
+
+        if let Some(trap) = &trap {
+            // The program counter has already moved past the trapping instruction. An
+            // exception in a delay slot reports the branch instead, as the slot is re-executed
+            // along with it.
+            let pc = self.get_pc() as u32 - 4;
+            if delayed_branch.is_some() {
+                self.cp0.record_exception(trap.code, pc - 4, true);
+            } else {
+                self.cp0.record_exception(trap.code, pc, false);
             }
+
+            // Exceptions clear the link bit, so that a pending `sc` fails.
+            self.set_link(None);
+        } else if delayed_branch.is_some() && self.get_input_request().is_some() {
+            // A read syscall in the delay slot is waiting for input and will run again, so
+            // the branch waits for it.
+            self.set_delayed_branch(delayed_branch);
+        } else if let Some(target) = delayed_branch {
+            self.set_pc(target);
+        }
+
+        // The load from the previous instruction completes, unless this instruction wrote
+        // the same register.
+        if let Some(load) = self.get_load_delay_slot() {
+            self.set_load_delay_slot(None);
+            self.set_register(load.register, load.value)?;
         }
 
         Ok((self.decode_instruction(instruction)?, trap)) // re-decode instruction because borrow checker or whatever
@@ -168,6 +437,14 @@ impl VM {
             return Ok(task);
         }
 
+        if let Some(task) = self.get_div_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_compare_task(instruction) {
+            return Ok(task);
+        }
+
         if let Some(task) = self.get_boolean_task(instruction) {
             return Ok(task);
         }
@@ -176,6 +453,34 @@ impl VM {
             return Ok(task);
         }
 
+        if let Some(task) = self.get_bit_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_move_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_memory_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_float_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_float_move_task(instruction) {
+            return Ok(task);
+        }
+
+        if let Some(task) = self.get_branch_task(instruction) {
+            return Ok(task);
+        }
+
+        if instruction.base.name == "syscall" {
+            return Ok(ExecutionTask::Syscall);
+        }
+
         Err(RuntimeError::new(
             FatalErrorType::IllegalInstruction,
             format!("Unsupported instruction \"{}\"", instruction.base.name),
@@ -277,6 +582,76 @@ impl VM {
                     signed: false,
                 })
             }
+            "mul" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Mul {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                })
+            }
+            "madd" | "maddu" | "msub" | "msubu" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::MultAccumulate {
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                    signed: !instruction.base.name.ends_with('u'),
+                    subtract: instruction.base.name.starts_with("msub"),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_div_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "div" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Div {
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                    signed: true,
+                })
+            }
+            "divu" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Div {
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                    signed: false,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_compare_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "slt" | "sltu" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::SetLessThan {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                    signed: instruction.base.name == "slt",
+                })
+            }
+            "slti" | "sltiu" => {
+                let args = coerce_i_format(instruction);
+
+                // sltiu sign-extends its immediate too, but then compares unsigned.
+                Some(ExecutionTask::SetLessThan {
+                    dest: Target::Register(args.rt),
+                    a: Target::Register(args.rs),
+                    b: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    signed: instruction.base.name == "slti",
+                })
+            }
             _ => None,
         }
     }
@@ -418,74 +793,639 @@ impl VM {
                     shift_type: ShiftType::Logical,
                 })
             }
+            "rotr" => {
+                let args = coerece_r_format(instruction);
 
-            _ => None,
-        }
-    }
+                Some(ExecutionTask::Shift {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rt),
+                    b: Target::Immediate(args.shamt as u16, HalfWordExtension::Zero),
+                    direction: ShiftDirection::Right,
+                    shift_type: ShiftType::Rotate,
+                })
+            }
+            "rotrv" => {
+                let args = coerece_r_format(instruction);
 
-    /// Gets the value of the given memory target.
-    fn get_value_of_target(&self, target: &Target) -> Result<u32, RuntimeError> {
-        match target {
-            Target::Register(reg) => self.get_register(*reg),
-            Target::Memory(address) => self.memory.get_word(*address as usize),
-            Target::Immediate(value, hw_ext) => match hw_ext {
-                HalfWordExtension::Sign => Ok((*value as i16) as u32),
-                HalfWordExtension::Zero => Ok(*value as u32),
-            },
-        }
-    }
+                Some(ExecutionTask::Shift {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rt),
+                    b: Target::Register(args.rs),
+                    direction: ShiftDirection::Right,
+                    shift_type: ShiftType::Rotate,
+                })
+            }
+            "lui" => {
+                let args = coerce_i_format(instruction);
 
-    /// Sets the value of the given memory target.
-    ///
-    /// Panics if the target is an immediate value.
-    fn set_value_of_target(&mut self, target: Target, value: u32) -> Result<(), RuntimeError> {
-        match target {
-            Target::Register(reg) => self.set_register(reg, value),
-            Target::Memory(address) => self.memory.set_word(address as usize, value),
-            Target::Immediate(_, _) => panic!("Cannot set value of immediate target"),
+                // Loading the upper half of a register is a left shift of the immediate.
+                Some(ExecutionTask::Shift {
+                    dest: Target::Register(args.rt),
+                    a: Target::Immediate(args.imm, HalfWordExtension::Zero),
+                    b: Target::Immediate(16, HalfWordExtension::Zero),
+                    direction: ShiftDirection::Left,
+                    shift_type: ShiftType::Logical,
+                })
+            }
+
+            _ => None,
         }
     }
 
-    /// Executes the given execution task.
-    #[allow(unreachable_patterns)]
-    fn execute_task(&mut self, task: ExecutionTask) -> Result<Option<Trap>, RuntimeError> {
-        match task {
-            ExecutionTask::Add {
-                dest,
-                a,
-                b,
-                overflow_trap: overflow,
-            } => {
-                let a = self.get_value_of_target(&a)?;
-                let b = self.get_value_of_target(&b)?;
+    fn get_bit_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "clz" | "clo" => {
+                let args = coerece_r_format(instruction);
 
-                let (result, overflowed) = a.overflowing_add(b);
+                Some(ExecutionTask::CountLeading {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rs),
+                    ones: instruction.base.name == "clo",
+                })
+            }
+            "seb" | "seh" => {
+                let args = coerece_r_format(instruction);
 
-                if overflow && overflowed {
-                    return Ok(Some(Trap::new(format!(
-                        "Overflowed when adding {} and {}",
-                        a, b
-                    ))));
-                }
+                Some(ExecutionTask::SignExtend {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rt),
+                    size: if instruction.base.name == "seb" { 1 } else { 2 },
+                })
+            }
+            "wsbh" => {
+                let args = coerece_r_format(instruction);
 
-                self.set_value_of_target(dest, result)?;
+                Some(ExecutionTask::SwapBytes {
+                    dest: Target::Register(args.rd),
+                    a: Target::Register(args.rt),
+                })
             }
-            ExecutionTask::Sub {
-                dest,
-                a,
+            "ext" => {
+                let args = coerece_r_format(instruction);
+
+                // The rd field holds the size minus one, and shamt holds the position.
+                Some(ExecutionTask::ExtractBits {
+                    dest: Target::Register(args.rt),
+                    a: Target::Register(args.rs),
+                    pos: args.shamt,
+                    size: args.rd + 1,
+                })
+            }
+            "ins" => {
+                let args = coerece_r_format(instruction);
+
+                // The rd field holds the position of the most significant bit to replace.
+                Some(ExecutionTask::InsertBits {
+                    dest: Target::Register(args.rt),
+                    a: Target::Register(args.rs),
+                    pos: args.shamt,
+                    size: (args.rd + 1).saturating_sub(args.shamt),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_move_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "mfhi" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::Register(args.rd),
+                    src: Target::Hi,
+                })
+            }
+            "mflo" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::Register(args.rd),
+                    src: Target::Lo,
+                })
+            }
+            "mthi" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::Hi,
+                    src: Target::Register(args.rs),
+                })
+            }
+            "mtlo" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::Lo,
+                    src: Target::Register(args.rs),
+                })
+            }
+            "movn" | "movz" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::ConditionalMove {
+                    dest: Target::Register(args.rd),
+                    src: Target::Register(args.rs),
+                    condition: Target::Register(args.rt),
+                    if_zero: instruction.base.name == "movz",
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_memory_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        let name = instruction.base.name;
+
+        match name {
+            "lb" | "lbu" | "lh" | "lhu" | "lw" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Load {
+                    dest: Target::Register(args.rt),
+                    src_addr: Target::Register(args.rs),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    signed: !name.ends_with('u'),
+                    size: match name {
+                        "lb" | "lbu" => 1,
+                        "lh" | "lhu" => 2,
+                        _ => 4,
+                    },
+                })
+            }
+            "sb" | "sh" | "sw" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Store {
+                    dest_addr: Target::Register(args.rs),
+                    src: Target::Register(args.rt),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    size: match name {
+                        "sb" => 1,
+                        "sh" => 2,
+                        _ => 4,
+                    },
+                })
+            }
+            "lwl" | "lwr" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::LoadPartial {
+                    dest: Target::Register(args.rt),
+                    src_addr: Target::Register(args.rs),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    left: name == "lwl",
+                })
+            }
+            "swl" | "swr" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::StorePartial {
+                    dest_addr: Target::Register(args.rs),
+                    src: Target::Register(args.rt),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    left: name == "swl",
+                })
+            }
+            "ll" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::LoadLinked {
+                    dest: Target::Register(args.rt),
+                    src_addr: Target::Register(args.rs),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            "sc" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::StoreConditional {
+                    dest_addr: Target::Register(args.rs),
+                    src: Target::Register(args.rt),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_float_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        // Floating point instructions are named "op.fmt", "op.to.from" or "c.cond.fmt".
+        let mut parts = instruction.base.name.split('.');
+
+        let (op, from, to) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(op), Some(from), None) => (op, from, from),
+            (Some("c"), Some(condition), Some(from)) => (condition, from, from),
+            (Some(op), Some(to), Some(from)) => (op, from, to),
+            _ => return None,
+        };
+
+        let format_of = |suffix| match suffix {
+            "s" => Some(FloatFormat::Single),
+            "d" => Some(FloatFormat::Double),
+            "w" => Some(FloatFormat::Word),
+            _ => None,
+        };
+        let from = format_of(from)?;
+        let to = format_of(to)?;
+
+        // Floating point instructions use the rt, rd and shamt fields as ft, fs and fd.
+        let args = coerece_r_format(instruction);
+        let (ft, fs, fd) = (args.rt, args.rd, args.shamt);
+
+        let binary = |op| ExecutionTask::FloatBinary {
+            op,
+            format: from,
+            dest: fd,
+            a: fs,
+            b: ft,
+        };
+        let unary = |op| ExecutionTask::FloatUnary {
+            op,
+            format: from,
+            dest: fd,
+            src: fs,
+        };
+        let convert = |rounding| ExecutionTask::FloatConvert {
+            from,
+            to,
+            dest: fd,
+            src: fs,
+            rounding,
+        };
+        let compare = |condition| ExecutionTask::FloatCompare {
+            condition,
+            format: from,
+            a: fs,
+            b: ft,
+            // The condition flag is held in the upper three bits of fd.
+            flag: fd >> 2,
+        };
+        let conditional_move = |condition| ExecutionTask::FloatMove {
+            format: from,
+            dest: fd,
+            src: fs,
+            condition,
+        };
+
+        match op {
+            "add" => Some(binary(FloatOperation::Add)),
+            "sub" => Some(binary(FloatOperation::Sub)),
+            "mul" => Some(binary(FloatOperation::Mul)),
+            "div" => Some(binary(FloatOperation::Div)),
+            "sqrt" => Some(unary(FloatUnaryOperation::Sqrt)),
+            "abs" => Some(unary(FloatUnaryOperation::Abs)),
+            "neg" => Some(unary(FloatUnaryOperation::Neg)),
+            "cvt" => Some(convert(RoundingMode::Nearest)),
+            "round" => Some(convert(RoundingMode::Nearest)),
+            "trunc" => Some(convert(RoundingMode::Zero)),
+            "ceil" => Some(convert(RoundingMode::Up)),
+            "floor" => Some(convert(RoundingMode::Down)),
+            "eq" => Some(compare(FloatCondition::Equal)),
+            "lt" => Some(compare(FloatCondition::LessThan)),
+            "le" => Some(compare(FloatCondition::LessOrEqual)),
+            "mov" => Some(conditional_move(None)),
+            // The condition flag is held in the upper three bits of ft.
+            "movf" => Some(conditional_move(Some((
+                Target::ConditionFlag(ft >> 2),
+                true,
+            )))),
+            "movt" => Some(conditional_move(Some((
+                Target::ConditionFlag(ft >> 2),
+                false,
+            )))),
+            "movz" => Some(conditional_move(Some((Target::Register(ft), true)))),
+            "movn" => Some(conditional_move(Some((Target::Register(ft), false)))),
+            _ => None,
+        }
+    }
+
+    /// Gets the execution task for moves between the integer and floating point units, floating
+    /// point loads and stores, and branches and moves on floating point conditions.
+    fn get_float_move_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "mfc1" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::Register(args.rt),
+                    src: Target::FloatRegister(args.rd),
+                })
+            }
+            "mtc1" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Move {
+                    dest: Target::FloatRegister(args.rd),
+                    src: Target::Register(args.rt),
+                })
+            }
+            "movf" | "movt" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::ConditionalMove {
+                    dest: Target::Register(args.rd),
+                    src: Target::Register(args.rs),
+                    condition: Target::ConditionFlag(args.rt >> 2),
+                    if_zero: instruction.base.name == "movf",
+                })
+            }
+            "lwc1" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Load {
+                    dest: Target::FloatRegister(args.rt),
+                    src_addr: Target::Register(args.rs),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    signed: false,
+                    size: 4,
+                })
+            }
+            "swc1" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Store {
+                    dest_addr: Target::Register(args.rs),
+                    src: Target::FloatRegister(args.rt),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                    size: 4,
+                })
+            }
+            "ldc1" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::LoadDouble {
+                    dest: args.rt,
+                    src_addr: Target::Register(args.rs),
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            "sdc1" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::StoreDouble {
+                    dest_addr: Target::Register(args.rs),
+                    src: args.rt,
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            "bc1f" | "bc1t" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Branch {
+                    a: Target::ConditionFlag(args.rt >> 2),
+                    b: Target::Immediate(
+                        (instruction.base.name == "bc1t") as u16,
+                        HalfWordExtension::Zero,
+                    ),
+                    comparison: Comparison::Equal,
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn get_branch_task(&self, instruction: &InstructionData) -> Option<ExecutionTask> {
+        match instruction.base.name {
+            "beq" | "bne" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Branch {
+                    a: Target::Register(args.rs),
+                    b: Target::Register(args.rt),
+                    comparison: if instruction.base.name == "beq" {
+                        Comparison::Equal
+                    } else {
+                        Comparison::NotEqual
+                    },
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            "blez" | "bgtz" => {
+                let args = coerce_i_format(instruction);
+
+                Some(ExecutionTask::Branch {
+                    a: Target::Register(args.rs),
+                    b: Target::Register(0),
+                    comparison: if instruction.base.name == "blez" {
+                        Comparison::LessOrEqual
+                    } else {
+                        Comparison::GreaterThan
+                    },
+                    offset: Target::Immediate(args.imm, HalfWordExtension::Sign),
+                })
+            }
+            "j" | "jal" => {
+                let args = coerce_j_format(instruction);
+
+                // The target replaces the low 28 bits of the address of the next instruction.
+                let region = self.get_pc() as u32 & 0xf0000000;
+
+                Some(ExecutionTask::Jump {
+                    dest: Target::Address(region | (args.address << 2)),
+                    link: if instruction.base.name == "jal" {
+                        Some(31)
+                    } else {
+                        None
+                    },
+                })
+            }
+            "jr" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Jump {
+                    dest: Target::Register(args.rs),
+                    link: None,
+                })
+            }
+            "jalr" => {
+                let args = coerece_r_format(instruction);
+
+                Some(ExecutionTask::Jump {
+                    dest: Target::Register(args.rs),
+                    link: Some(args.rd),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Transfers control to `target`, either immediately or after the next instruction if
+    /// delayed branching is enabled.
+    fn branch_to(&mut self, target: usize) {
+        if self.options.delayed_branching {
+            self.set_delayed_branch(Some(target));
+        } else {
+            self.set_pc(target);
+        }
+    }
+
+    /// The address of the instruction that a jump-and-link returns to.
+    fn get_return_address(&self) -> u32 {
+        // With delay slots, the return skips over the slot, as it has already executed.
+        if self.options.delayed_branching {
+            self.get_pc() as u32 + 4
+        } else {
+            self.get_pc() as u32
+        }
+    }
+
+    /// Gets the value of the given memory target.
+    ///
+    /// Reading a register in the delay slot of a load raises a warning, and gives the value
+    /// from before the load.
+    fn get_value_of_target(&mut self, target: &Target) -> Result<u32, RuntimeError> {
+        match target {
+            Target::Register(reg) => {
+                if let Some(load) = self.get_load_delay_slot() {
+                    if load.register == *reg && !load.hazard {
+                        self.warn(format!(
+                            "${} is read at {:#010x} in the delay slot of the load at {:#010x}, so \
+                             the old value is used",
+                            reg,
+                            self.get_pc() - 4,
+                            load.address
+                        ));
+                        self.set_load_delay_slot(Some(DelayedLoad {
+                            hazard: true,
+                            ..load
+                        }));
+                    }
+                }
+
+                self.get_register(*reg)
+            }
+            Target::Memory(address) => self.memory.get_word(*address as usize),
+            Target::Immediate(value, hw_ext) => match hw_ext {
+                HalfWordExtension::Sign => Ok((*value as i16) as u32),
+                HalfWordExtension::Zero => Ok(*value as u32),
+            },
+            Target::Hi => Ok(self.get_hi()),
+            Target::Lo => Ok(self.get_lo()),
+            Target::FloatRegister(reg) => self.fpu.get_register(*reg),
+            Target::ConditionFlag(flag) => Ok(self.fpu.get_condition_flag(*flag) as u32),
+            Target::Address(address) => Ok(*address),
+        }
+    }
+
+    /// Sets the value of the given memory target.
+    ///
+    /// Panics if the target is an immediate value.
+    fn set_value_of_target(&mut self, target: Target, value: u32) -> Result<(), RuntimeError> {
+        match target {
+            // Writes to the zero register are discarded.
+            Target::Register(0) => Ok(()),
+            Target::Register(reg) => {
+                // A write in the delay slot of a load lands after the load does.
+                if let Some(load) = self.get_load_delay_slot() {
+                    if load.register == reg {
+                        self.set_load_delay_slot(None);
+                    }
+                }
+
+                self.set_register(reg, value)
+            }
+            Target::Memory(address) => self.memory.set_word(address as usize, value),
+            Target::Immediate(_, _) | Target::Address(_) => {
+                panic!("Cannot set value of immediate target")
+            }
+            Target::Hi => {
+                self.set_hi(value);
+                Ok(())
+            }
+            Target::Lo => {
+                self.set_lo(value);
+                Ok(())
+            }
+            Target::FloatRegister(reg) => self.fpu.set_register(reg, value),
+            Target::ConditionFlag(flag) => {
+                self.fpu.set_condition_flag(flag, value != 0);
+                Ok(())
+            }
+        }
+    }
+
+    /// Gets the address given by a base address and an offset.
+    fn get_effective_address(
+        &mut self,
+        base: &Target,
+        offset: &Target,
+    ) -> Result<usize, RuntimeError> {
+        let base = self.get_value_of_target(base)?;
+        let offset = self.get_value_of_target(offset)?;
+
+        Ok(base.wrapping_add(offset) as usize)
+    }
+
+    /// Writes the result of a load to `dest`. With load delays enabled, the write is held back
+    /// until after the next instruction.
+    fn write_loaded_value(&mut self, dest: Target, value: u32) -> Result<(), RuntimeError> {
+        match dest {
+            Target::Register(register) if self.options.load_delay && register != 0 => {
+                self.set_delayed_load(Some(DelayedLoad {
+                    register,
+                    value,
+                    address: self.get_pc() - 4,
+                    hazard: false,
+                }));
+                Ok(())
+            }
+            _ => self.set_value_of_target(dest, value),
+        }
+    }
+
+    /// Clears the link bit if a store of `size` bytes at `address` touches the linked word.
+    fn check_link(&mut self, address: usize, size: usize) {
+        if let Some(linked) = self.get_link() {
+            if address < linked + 4 && linked < address + size {
+                self.set_link(None);
+            }
+        }
+    }
+
+    /// Executes the given execution task.
+    #[allow(unreachable_patterns)]
+    fn execute_task(&mut self, task: ExecutionTask) -> Result<Option<Trap>, RuntimeError> {
+        match task {
+            ExecutionTask::Add {
+                dest,
+                a,
+                b,
+                overflow_trap: overflow,
+            } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                let (result, overflowed) = (a as i32).overflowing_add(b as i32);
+                let result = result as u32;
+
+                if overflow && overflowed {
+                    return Ok(Some(Trap::new(
+                        ExceptionCode::Overflow,
+                        format!("Overflowed when adding {} and {}", a, b),
+                    )));
+                }
+
+                self.set_value_of_target(dest, result)?;
+            }
+            ExecutionTask::Sub {
+                dest,
+                a,
                 b,
                 overflow_trap: overflow,
             } => {
                 let a = self.get_value_of_target(&a)?;
                 let b = self.get_value_of_target(&b)?;
 
-                let (result, overflowed) = a.overflowing_sub(b);
+                let (result, overflowed) = (a as i32).overflowing_sub(b as i32);
+                let result = result as u32;
 
                 if overflow && overflowed {
-                    return Ok(Some(Trap::new(format!(
-                        "Overflowed when subtracting {} from {}",
-                        a, b
-                    ))));
+                    return Ok(Some(Trap::new(
+                        ExceptionCode::Overflow,
+                        format!("Overflowed when subtracting {} from {}", a, b),
+                    )));
                 }
 
                 self.set_value_of_target(dest, result)?;
@@ -503,6 +1443,68 @@ impl VM {
                 self.set_hi((result >> 32) as u32);
                 self.set_lo(result as u32);
             }
+            ExecutionTask::Mul { dest, a, b } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                let result = (a as i32 as i64).wrapping_mul(b as i32 as i64) as u64;
+
+                self.set_hi((result >> 32) as u32);
+                self.set_lo(result as u32);
+                self.set_value_of_target(dest, result as u32)?;
+            }
+            ExecutionTask::MultAccumulate {
+                a,
+                b,
+                signed,
+                subtract,
+            } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                let product = if signed {
+                    (a as i32 as i64).wrapping_mul(b as i32 as i64) as u64
+                } else {
+                    (a as u64).wrapping_mul(b as u64)
+                };
+                let accumulator = ((self.get_hi() as u64) << 32) | self.get_lo() as u64;
+
+                let result = if subtract {
+                    accumulator.wrapping_sub(product)
+                } else {
+                    accumulator.wrapping_add(product)
+                };
+
+                self.set_hi((result >> 32) as u32);
+                self.set_lo(result as u32);
+            }
+            ExecutionTask::Div { a, b, signed } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                // Division by zero leaves HI and LO unchanged, as in MARS.
+                if b != 0 {
+                    if signed {
+                        self.set_lo((a as i32).wrapping_div(b as i32) as u32);
+                        self.set_hi((a as i32).wrapping_rem(b as i32) as u32);
+                    } else {
+                        self.set_lo(a / b);
+                        self.set_hi(a % b);
+                    }
+                }
+            }
+            ExecutionTask::SetLessThan { dest, a, b, signed } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                let less = if signed {
+                    (a as i32) < (b as i32)
+                } else {
+                    a < b
+                };
+
+                self.set_value_of_target(dest, less as u32)?;
+            }
             ExecutionTask::And { dest, a, b } => {
                 let a = self.get_value_of_target(&a)?;
                 let b = self.get_value_of_target(&b)?;
@@ -521,6 +1523,12 @@ impl VM {
 
                 self.set_value_of_target(dest, a ^ b)?;
             }
+            ExecutionTask::Nor { dest, a, b } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                self.set_value_of_target(dest, !(a | b))?;
+            }
             ExecutionTask::Shift {
                 dest,
                 a,
@@ -529,20 +1537,529 @@ impl VM {
                 shift_type,
             } => {
                 let a = self.get_value_of_target(&a)?;
-                let b = self.get_value_of_target(&b)?;
+                // Only the lowest five bits of a variable shift amount are used.
+                let b = self.get_value_of_target(&b)? & 0b11111;
 
                 let result = match (direction, shift_type) {
                     (ShiftDirection::Left, ShiftType::Logical) => a << b,
                     (ShiftDirection::Left, ShiftType::Arithmetic) => ((a as i32) << b) as u32,
+                    (ShiftDirection::Left, ShiftType::Rotate) => a.rotate_left(b),
                     (ShiftDirection::Right, ShiftType::Logical) => a >> b,
                     (ShiftDirection::Right, ShiftType::Arithmetic) => ((a as i32) >> b) as u32,
+                    (ShiftDirection::Right, ShiftType::Rotate) => a.rotate_right(b),
                 };
 
                 self.set_value_of_target(dest, result)?;
             }
+            ExecutionTask::CountLeading { dest, a, ones } => {
+                let a = self.get_value_of_target(&a)?;
+
+                let count = if ones {
+                    a.leading_ones()
+                } else {
+                    a.leading_zeros()
+                };
+
+                self.set_value_of_target(dest, count)?;
+            }
+            ExecutionTask::SignExtend { dest, a, size } => {
+                let a = self.get_value_of_target(&a)?;
+
+                let result = match size {
+                    1 => a as u8 as i8 as u32,
+                    2 => a as u16 as i16 as u32,
+                    _ => a,
+                };
+
+                self.set_value_of_target(dest, result)?;
+            }
+            ExecutionTask::SwapBytes { dest, a } => {
+                let a = self.get_value_of_target(&a)?;
+
+                let result = ((a & 0x00ff00ff) << 8) | ((a & 0xff00ff00) >> 8);
+
+                self.set_value_of_target(dest, result)?;
+            }
+            ExecutionTask::ExtractBits { dest, a, pos, size } => {
+                check_bit_field(pos, size)?;
+
+                let a = self.get_value_of_target(&a)?;
+                let mask = bit_mask(size);
+
+                self.set_value_of_target(dest, (a >> pos) & mask)?;
+            }
+            ExecutionTask::InsertBits { dest, a, pos, size } => {
+                check_bit_field(pos, size)?;
+
+                let a = self.get_value_of_target(&a)?;
+                let original = self.get_value_of_target(&dest)?;
+                let mask = bit_mask(size) << pos;
+
+                self.set_value_of_target(dest, (original & !mask) | ((a << pos) & mask))?;
+            }
+            ExecutionTask::Move { dest, src } => {
+                let value = self.get_value_of_target(&src)?;
+
+                self.set_value_of_target(dest, value)?;
+            }
+            ExecutionTask::ConditionalMove {
+                dest,
+                src,
+                condition,
+                if_zero,
+            } => {
+                let condition = self.get_value_of_target(&condition)?;
+
+                if (condition == 0) == if_zero {
+                    let value = self.get_value_of_target(&src)?;
+
+                    self.set_value_of_target(dest, value)?;
+                }
+            }
+            ExecutionTask::Load {
+                dest,
+                src_addr,
+                offset,
+                signed,
+                size,
+            } => {
+                let address = self.get_effective_address(&src_addr, &offset)?;
+
+                let value = match (size, signed) {
+                    (1, true) => self.memory.get_byte(address)? as i8 as u32,
+                    (1, false) => self.memory.get_byte(address)? as u32,
+                    (2, true) => self.memory.get_halfword(address)? as i16 as u32,
+                    (2, false) => self.memory.get_halfword(address)? as u32,
+                    _ => self.memory.get_word(address)?,
+                };
+
+                self.write_loaded_value(dest, value)?;
+            }
+            ExecutionTask::Store {
+                dest_addr,
+                src,
+                offset,
+                size,
+            } => {
+                let address = self.get_effective_address(&dest_addr, &offset)?;
+                let value = self.get_value_of_target(&src)?;
+
+                match size {
+                    1 => self.memory.set_byte(address, value as u8)?,
+                    2 => self.memory.set_halfword(address, value as u16)?,
+                    _ => self.memory.set_word(address, value)?,
+                }
+
+                self.check_link(address, size as usize);
+            }
+            ExecutionTask::LoadPartial {
+                dest,
+                src_addr,
+                offset,
+                left,
+            } => {
+                let address = self.get_effective_address(&src_addr, &offset)?;
+
+                // lwl and lwr can be issued back to back, as the second merges into the
+                // result of the first even while it is still in its delay slot.
+                let original = match (self.get_load_delay_slot(), dest) {
+                    (Some(load), Target::Register(reg)) if load.register == reg => load.value,
+                    _ => self.get_value_of_target(&dest)?,
+                };
+
+                let value = if left {
+                    self.memory.get_word_left(address, original)?
+                } else {
+                    self.memory.get_word_right(address, original)?
+                };
+
+                self.write_loaded_value(dest, value)?;
+            }
+            ExecutionTask::StorePartial {
+                dest_addr,
+                src,
+                offset,
+                left,
+            } => {
+                let address = self.get_effective_address(&dest_addr, &offset)?;
+                let value = self.get_value_of_target(&src)?;
+
+                if left {
+                    self.memory.set_word_left(address, value)?;
+                } else {
+                    self.memory.set_word_right(address, value)?;
+                }
+
+                self.check_link(address - address % 4, 4);
+            }
+            ExecutionTask::LoadLinked {
+                dest,
+                src_addr,
+                offset,
+            } => {
+                let address = self.get_effective_address(&src_addr, &offset)?;
+                let value = self.memory.get_word(address)?;
+
+                self.write_loaded_value(dest, value)?;
+                self.set_link(Some(address));
+            }
+            ExecutionTask::StoreConditional {
+                dest_addr,
+                src,
+                offset,
+            } => {
+                let address = self.get_effective_address(&dest_addr, &offset)?;
+                let success = self.get_link() == Some(address);
+
+                if success {
+                    let value = self.get_value_of_target(&src)?;
+                    self.memory.set_word(address, value)?;
+                }
+
+                self.set_link(None);
+                self.set_value_of_target(src, success as u32)?;
+            }
+            ExecutionTask::LoadDouble {
+                dest,
+                src_addr,
+                offset,
+            } => {
+                let address = self.get_effective_address(&src_addr, &offset)?;
+                let value = self.memory.get_doubleword(address)?;
+
+                self.fpu.set_double_bits(dest, value)?;
+            }
+            ExecutionTask::StoreDouble {
+                dest_addr,
+                src,
+                offset,
+            } => {
+                let address = self.get_effective_address(&dest_addr, &offset)?;
+                let value = self.fpu.get_double_bits(src)?;
+
+                self.memory.set_doubleword(address, value)?;
+                self.check_link(address, 8);
+            }
+            ExecutionTask::FloatBinary {
+                op,
+                format,
+                dest,
+                a,
+                b,
+            } => {
+                let apply = |a: f64, b: f64| match op {
+                    FloatOperation::Add => a + b,
+                    FloatOperation::Sub => a - b,
+                    FloatOperation::Mul => a * b,
+                    FloatOperation::Div => a / b,
+                };
+
+                if format == FloatFormat::Double {
+                    let result = apply(self.fpu.get_double(a)?, self.fpu.get_double(b)?);
+                    self.fpu.set_double(dest, result)?;
+                } else {
+                    // Single precision results are computed exactly in double precision, and
+                    // then rounded once.
+                    let a = self.fpu.get_float(a)? as f64;
+                    let b = self.fpu.get_float(b)? as f64;
+                    self.fpu.set_float(dest, apply(a, b) as f32)?;
+                }
+            }
+            ExecutionTask::FloatUnary {
+                op,
+                format,
+                dest,
+                src,
+            } => {
+                if format == FloatFormat::Double {
+                    let value = self.fpu.get_double(src)?;
+
+                    self.fpu.set_double(
+                        dest,
+                        match op {
+                            FloatUnaryOperation::Sqrt => value.sqrt(),
+                            FloatUnaryOperation::Abs => value.abs(),
+                            FloatUnaryOperation::Neg => -value,
+                        },
+                    )?;
+                } else {
+                    let value = self.fpu.get_float(src)?;
+
+                    self.fpu.set_float(
+                        dest,
+                        match op {
+                            FloatUnaryOperation::Sqrt => value.sqrt(),
+                            FloatUnaryOperation::Abs => value.abs(),
+                            FloatUnaryOperation::Neg => -value,
+                        },
+                    )?;
+                }
+            }
+            ExecutionTask::FloatMove {
+                format,
+                dest,
+                src,
+                condition,
+            } => {
+                if let Some((condition, if_zero)) = condition {
+                    if (self.get_value_of_target(&condition)? == 0) != if_zero {
+                        return Ok(None);
+                    }
+                }
+
+                if format == FloatFormat::Double {
+                    let value = self.fpu.get_double_bits(src)?;
+                    self.fpu.set_double_bits(dest, value)?;
+                } else {
+                    let value = self.fpu.get_register(src)?;
+                    self.fpu.set_register(dest, value)?;
+                }
+            }
+            ExecutionTask::FloatConvert {
+                from,
+                to,
+                dest,
+                src,
+                rounding,
+            } => {
+                let value = match from {
+                    FloatFormat::Single => self.fpu.get_float(src)? as f64,
+                    FloatFormat::Double => self.fpu.get_double(src)?,
+                    FloatFormat::Word => self.fpu.get_register(src)? as i32 as f64,
+                };
+
+                match to {
+                    FloatFormat::Single => self.fpu.set_float(dest, value as f32)?,
+                    FloatFormat::Double => self.fpu.set_double(dest, value)?,
+                    FloatFormat::Word => {
+                        let rounded = match rounding {
+                            RoundingMode::Nearest => value.round_ties_even(),
+                            RoundingMode::Zero => value.trunc(),
+                            RoundingMode::Up => value.ceil(),
+                            RoundingMode::Down => value.floor(),
+                        };
+
+                        // NaN and out of range values convert to the largest positive integer.
+                        let result = if rounded >= i32::MIN as f64 && rounded <= i32::MAX as f64 {
+                            rounded as i32
+                        } else {
+                            i32::MAX
+                        };
+
+                        self.fpu.set_register(dest, result as u32)?;
+                    }
+                }
+            }
+            ExecutionTask::FloatCompare {
+                condition,
+                format,
+                a,
+                b,
+                flag,
+            } => {
+                let (a, b) = if format == FloatFormat::Double {
+                    (self.fpu.get_double(a)?, self.fpu.get_double(b)?)
+                } else {
+                    (self.fpu.get_float(a)? as f64, self.fpu.get_float(b)? as f64)
+                };
+
+                let result = match condition {
+                    FloatCondition::Equal => a == b,
+                    FloatCondition::LessThan => a < b,
+                    FloatCondition::LessOrEqual => a <= b,
+                };
+
+                self.fpu.set_condition_flag(flag, result);
+            }
+            ExecutionTask::Branch {
+                a,
+                b,
+                comparison,
+                offset,
+            } => {
+                let a = self.get_value_of_target(&a)?;
+                let b = self.get_value_of_target(&b)?;
+
+                let taken = match comparison {
+                    Comparison::Equal => a == b,
+                    Comparison::NotEqual => a != b,
+                    Comparison::LessOrEqual => a as i32 <= b as i32,
+                    Comparison::GreaterThan => a as i32 > b as i32,
+                };
+
+                if taken {
+                    // The offset is relative to the instruction after the branch, which is
+                    // also its delay slot.
+                    let offset = self.get_value_of_target(&offset)? << 2;
+                    self.branch_to(self.get_pc().wrapping_add(offset as i32 as usize));
+                }
+            }
+            ExecutionTask::Jump { dest, link } => {
+                // Read the destination before linking, as `jalr` may use the same register.
+                let dest = self.get_value_of_target(&dest)?;
+
+                if let Some(link) = link {
+                    self.set_value_of_target(Target::Register(link), self.get_return_address())?;
+                }
+
+                self.branch_to(dest as usize);
+            }
+            ExecutionTask::Syscall => return self.execute_syscall(),
             _ => panic!("Unsupported execution task {:?}", task),
         }
 
         Ok(None)
     }
 }
+
+/// Returns a mask of the lowest `size` bits.
+fn bit_mask(size: u8) -> u32 {
+    if size >= 32 {
+        u32::MAX
+    } else {
+        (1 << size) - 1
+    }
+}
+
+/// Checks that a bit field used by `ext` or `ins` lies within a word.
+fn check_bit_field(pos: u8, size: u8) -> Result<(), RuntimeError> {
+    if size == 0 || pos as u32 + size as u32 > 32 {
+        Err(RuntimeError::new(
+            FatalErrorType::IllegalInstruction,
+            format!("Invalid bit field of size {} at position {}", size, pos),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::loader::{vm_running, DATA};
+    use crate::runtime::vm::VM;
+
+    fn run(vm: &mut VM, count: usize) {
+        for _ in 0..count {
+            vm.run_single_instruction().unwrap();
+        }
+    }
+
+    #[test]
+    fn sc_succeeds_after_ll() {
+        // ll $t0, 0($t1); addiu $t2, $zero, 7; sc $t2, 0($t1)
+        let mut vm = vm_running(&[0xc1280000, 0x240a0007, 0xe12a0000]);
+        run(&mut vm, 3);
+
+        assert_eq!(vm.get_register(10).unwrap(), 1);
+        assert_eq!(vm.memory.get_word(DATA).unwrap(), 7);
+        assert_eq!(vm.get_link(), None);
+    }
+
+    #[test]
+    fn store_to_linked_word_makes_sc_fail() {
+        // ll $t0, 0($t1); sb $zero, 3($t1); addiu $t2, $zero, 7; sc $t2, 0($t1)
+        let mut vm = vm_running(&[0xc1280000, 0xa1200003, 0x240a0007, 0xe12a0000]);
+        vm.memory.set_word(DATA, 5).unwrap();
+        run(&mut vm, 4);
+
+        assert_eq!(vm.get_register(10).unwrap(), 0);
+        assert_eq!(vm.memory.get_word(DATA).unwrap(), 0);
+    }
+
+    #[test]
+    fn lwl_and_lwr_execute_back_to_back() {
+        // lwl $t0, 1($t1); lwr $t0, 4($t1)
+        let mut vm = vm_running(&[0x89280001, 0x99280004]);
+        vm.memory.set_word(DATA, 0x11223344).unwrap();
+        vm.memory.set_word(DATA + 4, 0x55667788).unwrap();
+        run(&mut vm, 2);
+
+        assert_eq!(vm.get_register(8).unwrap(), 0x22334455);
+    }
+
+    #[test]
+    fn single_precision_arithmetic_and_conversions() {
+        // add.s $f0, $f2, $f4; c.lt.s $f2, $f4; cvt.w.s $f6, $f0; trunc.w.s $f8, $f0;
+        // mfc1 $t0, $f6; mfc1 $t2, $f8
+        let mut vm = vm_running(&[
+            0x46041000, 0x4604103c, 0x460001a4, 0x4600020d, 0x44083000, 0x440a4000,
+        ]);
+        vm.fpu.set_float(2, 1.5).unwrap();
+        vm.fpu.set_float(4, 2.25).unwrap();
+        run(&mut vm, 6);
+
+        assert_eq!(vm.fpu.get_float(0).unwrap(), 3.75);
+        assert!(vm.fpu.get_condition_flag(0));
+        assert_eq!(vm.get_register(8).unwrap(), 4);
+        assert_eq!(vm.get_register(10).unwrap(), 3);
+    }
+
+    #[test]
+    fn bc1t_branches_on_the_condition_flag() {
+        // c.lt.s $f2, $f4; bc1t +1; addiu $t3, $zero, 1; addiu $t4, $zero, 1
+        let mut vm = vm_running(&[0x4604103c, 0x45010001, 0x240b0001, 0x240c0001]);
+        vm.fpu.set_float(4, 1.0).unwrap();
+        run(&mut vm, 3);
+
+        assert_eq!(vm.get_register(11).unwrap(), 0);
+        assert_eq!(vm.get_register(12).unwrap(), 1);
+    }
+
+    #[test]
+    fn double_loads_and_stores_use_register_pairs() {
+        // ldc1 $f2, 0($t1); mul.d $f4, $f2, $f2; sdc1 $f4, 8($t1)
+        let mut vm = vm_running(&[0xd5220000, 0x46221102, 0xf5240008]);
+        vm.memory
+            .set_doubleword(DATA, 1.5f64.to_bits())
+            .unwrap();
+        run(&mut vm, 3);
+
+        assert_eq!(vm.fpu.get_register(3).unwrap(), 0x3ff80000);
+        assert_eq!(
+            vm.memory.get_doubleword(DATA + 8).unwrap(),
+            2.25f64.to_bits()
+        );
+    }
+
+    #[test]
+    fn print_float_syscall() {
+        // addiu $v0, $zero, 2; syscall
+        let mut vm = vm_running(&[0x24020002, 0x0000000c]);
+        vm.fpu.set_float(12, 1.5).unwrap();
+        run(&mut vm, 2);
+
+        assert_eq!(vm.take_output(), "1.5");
+    }
+
+    #[test]
+    fn read_syscall_in_a_delay_slot_keeps_the_branch() {
+        // addiu $v0, $zero, 5; j 0x00400010; syscall; addiu $t0, $zero, 1;
+        // addiu $t1, $zero, 2
+        let mut vm = vm_running(&[0x24020005, 0x08100004, 0x0000000c, 0x24080001, 0x24090002]);
+        vm.options.delayed_branching = true;
+        run(&mut vm, 3);
+
+        assert!(vm.get_input_request().is_some());
+        assert_eq!(vm.get_pc(), 0x00400008);
+
+        vm.push_input("42\n");
+        run(&mut vm, 2);
+
+        assert_eq!(vm.get_v0(), 42);
+        assert_eq!(vm.get_register(8).unwrap(), 0);
+        assert_eq!(vm.get_register(9).unwrap(), 2);
+    }
+
+    #[test]
+    fn ll_result_is_delayed_like_other_loads() {
+        // ll $t0, 0($t1); addu $t2, $t0, $zero
+        let mut vm = vm_running(&[0xc1280000, 0x01005021]);
+        vm.options.load_delay = true;
+        vm.memory.set_word(DATA, 7).unwrap();
+        run(&mut vm, 2);
+
+        assert_eq!(vm.get_register(10).unwrap(), 0);
+        assert_eq!(vm.get_register(8).unwrap(), 7);
+        assert_eq!(vm.take_warnings().len(), 1);
+    }
+}
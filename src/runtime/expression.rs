@@ -0,0 +1,494 @@
+use crate::runtime::register_aliases::Register;
+use crate::runtime::vm::VM;
+
+/// A binary operator, listed from the loosest to the tightest binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOperator {
+    /// How tightly the operator binds. Operators with the same precedence are left
+    /// associative.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessOrEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterOrEqual => 3,
+            BinaryOperator::BitOr => 4,
+            BinaryOperator::BitXor => 5,
+            BinaryOperator::BitAnd => 6,
+            BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight => 7,
+            BinaryOperator::Add | BinaryOperator::Sub => 8,
+            BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Rem => 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    /// The number of times the breakpoint being checked has been reached.
+    Hits,
+    Symbol(String),
+    /// A sign-extended read of the given number of bytes.
+    Memory(u8, Box<Node>),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Register(String),
+    Identifier(String),
+    Operator(&'static str),
+    OpenBracket,
+    CloseBracket,
+    OpenParen,
+    CloseParen,
+}
+
+/// Operators, with longer operators first so that they are matched before their prefixes.
+const OPERATORS: [&str; 20] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "|", "^", "&", "+", "-", "*", "/",
+    "%", "!", "~",
+];
+
+/// An integer expression over the state of the VM, such as `$t0 == 5 && mem[$sp+4] > 0`,
+/// used by breakpoint conditions and watch expressions.
+///
+/// Values are 64-bit signed integers. Registers, memory, labels and numbers that fit in 32
+/// bits are sign-extended from 32 bits, so that `$t0 == 0xFFFFFFFF` holds when `$t0` is -1.
+/// Addresses wrap to 32 bits. Comparisons and logical operators give 1 for true and 0 for
+/// false. Memory is read with `byte[...]`, `half[...]` or `word[...]`, or `mem[...]` for a
+/// word. Identifiers other than `pc`, `hi`, `lo` and `hits` are looked up as labels.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            position: 0,
+        };
+
+        let root = parser.parse_expression(0)?;
+
+        if let Some(token) = parser.peek() {
+            return Err(format!("Unexpected {:?} in \"{}\"", token, source));
+        }
+
+        Ok(Expression {
+            source: source.to_owned(),
+            root,
+        })
+    }
+
+    /// The text the expression was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let word_length = rest[c.len_utf8()..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .map_or(rest.len(), |end| end + c.len_utf8());
+
+        let (token, length) = match c {
+            '[' => (Token::OpenBracket, 1),
+            ']' => (Token::CloseBracket, 1),
+            '(' => (Token::OpenParen, 1),
+            ')' => (Token::CloseParen, 1),
+            '$' => (
+                Token::Register(rest[1..word_length].to_owned()),
+                word_length,
+            ),
+            '0'..='9' => {
+                let text = &rest[..word_length];
+                let number = match text.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => text.parse(),
+                }
+                .map_err(|_| format!("Invalid number \"{}\"", text))?;
+
+                (Token::Number(sign_extend_word(number)), word_length)
+            }
+            _ if c.is_ascii_alphabetic() || c == '_' => (
+                Token::Identifier(rest[..word_length].to_owned()),
+                word_length,
+            ),
+            _ => match OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(*operator))
+            {
+                Some(operator) => (Token::Operator(operator), operator.len()),
+                None => return Err(format!("Unexpected character '{}'", c)),
+            },
+        };
+
+        tokens.push(token);
+        rest = &rest[length..];
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if *token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err(format!(
+                "Expected {:?} at the end of the expression",
+                expected
+            )),
+        }
+    }
+
+    /// Parse binary operations whose operators bind tighter than `min_precedence`.
+    fn parse_expression(&mut self, min_precedence: u8) -> Result<Node, String> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(Token::Operator(operator)) = self.peek() {
+            let operator = match binary_operator(operator) {
+                Some(operator) if operator.precedence() > min_precedence => operator,
+                _ => break,
+            };
+
+            self.next();
+            let right = self.parse_expression(operator.precedence())?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, String> {
+        let operator = match self.peek() {
+            Some(Token::Operator("-")) => UnaryOperator::Negate,
+            Some(Token::Operator("!")) => UnaryOperator::Not,
+            Some(Token::Operator("~")) => UnaryOperator::BitNot,
+            _ => return self.parse_primary(),
+        };
+
+        self.next();
+        Ok(Node::Unary(operator, Box::new(self.parse_unary()?)))
+    }
+
+    fn parse_primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Node::Number(*number)),
+            Some(Token::Register(name)) => Register::parse(name)
+                .map(Node::Register)
+                .ok_or_else(|| format!("Unknown register \"${}\"", name)),
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "pc" => Ok(Node::Register(Register::Pc)),
+                "hi" => Ok(Node::Register(Register::Hi)),
+                "lo" => Ok(Node::Register(Register::Lo)),
+                "hits" => Ok(Node::Hits),
+                "byte" | "half" | "word" | "mem" if self.peek() == Some(&Token::OpenBracket) => {
+                    let size = match name.as_str() {
+                        "byte" => 1,
+                        "half" => 2,
+                        _ => 4,
+                    };
+
+                    self.next();
+                    let address = self.parse_expression(0)?;
+                    self.expect(Token::CloseBracket)?;
+
+                    Ok(Node::Memory(size, Box::new(address)))
+                }
+                _ => Ok(Node::Symbol(name.clone())),
+            },
+            Some(Token::OpenParen) => {
+                let node = self.parse_expression(0)?;
+                self.expect(Token::CloseParen)?;
+                Ok(node)
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err(String::from("Unexpected end of expression")),
+        }
+    }
+}
+
+fn binary_operator(operator: &str) -> Option<BinaryOperator> {
+    Some(match operator {
+        "||" => BinaryOperator::Or,
+        "&&" => BinaryOperator::And,
+        "==" => BinaryOperator::Equal,
+        "!=" => BinaryOperator::NotEqual,
+        "<" => BinaryOperator::Less,
+        "<=" => BinaryOperator::LessOrEqual,
+        ">" => BinaryOperator::Greater,
+        ">=" => BinaryOperator::GreaterOrEqual,
+        "|" => BinaryOperator::BitOr,
+        "^" => BinaryOperator::BitXor,
+        "&" => BinaryOperator::BitAnd,
+        "<<" => BinaryOperator::ShiftLeft,
+        ">>" => BinaryOperator::ShiftRight,
+        "+" => BinaryOperator::Add,
+        "-" => BinaryOperator::Sub,
+        "*" => BinaryOperator::Mul,
+        "/" => BinaryOperator::Div,
+        "%" => BinaryOperator::Rem,
+        _ => return None,
+    })
+}
+
+/// Read a number that fits in 32 bits as a word, the way values in registers and memory
+/// are read.
+fn sign_extend_word(number: i64) -> i64 {
+    if (0..=u32::MAX as i64).contains(&number) {
+        number as u32 as i32 as i64
+    } else {
+        number
+    }
+}
+
+impl VM {
+    /// Evaluate an expression against the current state. `hits` is the hit count of the
+    /// breakpoint whose condition is being checked, if any.
+    pub fn evaluate(&self, expression: &Expression, hits: Option<u64>) -> Result<i64, String> {
+        self.evaluate_node(&expression.root, hits)
+    }
+
+    fn evaluate_node(&self, node: &Node, hits: Option<u64>) -> Result<i64, String> {
+        Ok(match node {
+            Node::Number(number) => *number,
+            Node::Register(Register::Pc) => self.get_pc() as u32 as i32 as i64,
+            Node::Register(register) => self
+                .get_any_register(*register)
+                .map_err(|err| err.message)? as i32 as i64,
+            Node::Hits => hits
+                .ok_or_else(|| String::from("hits is only defined in breakpoint conditions"))?
+                as i64,
+            Node::Symbol(name) => self
+                .debug_info
+                .symbol_address(name)
+                .ok_or_else(|| format!("Unknown symbol \"{}\"", name))?
+                as u32 as i32 as i64,
+            Node::Memory(size, address) => {
+                let address = self.evaluate_node(address, hits)? as u32 as usize;
+
+                match size {
+                    1 => self
+                        .memory
+                        .get_byte(address)
+                        .map(|value| value as i8 as i64),
+                    2 => self
+                        .memory
+                        .get_halfword(address)
+                        .map(|value| value as i16 as i64),
+                    _ => self
+                        .memory
+                        .get_word(address)
+                        .map(|value| value as i32 as i64),
+                }
+                .map_err(|err| err.message)?
+            }
+            Node::Unary(operator, operand) => {
+                let value = self.evaluate_node(operand, hits)?;
+
+                match operator {
+                    UnaryOperator::Negate => value.wrapping_neg(),
+                    UnaryOperator::Not => (value == 0) as i64,
+                    UnaryOperator::BitNot => !value,
+                }
+            }
+            // Logical operators short-circuit, so that `$a0 != 0 && mem[$a0] == 1` is safe.
+            Node::Binary(BinaryOperator::And, left, right) => {
+                (self.evaluate_node(left, hits)? != 0 && self.evaluate_node(right, hits)? != 0)
+                    as i64
+            }
+            Node::Binary(BinaryOperator::Or, left, right) => {
+                (self.evaluate_node(left, hits)? != 0 || self.evaluate_node(right, hits)? != 0)
+                    as i64
+            }
+            Node::Binary(operator, left, right) => {
+                let a = self.evaluate_node(left, hits)?;
+                let b = self.evaluate_node(right, hits)?;
+
+                match operator {
+                    BinaryOperator::Equal => (a == b) as i64,
+                    BinaryOperator::NotEqual => (a != b) as i64,
+                    BinaryOperator::Less => (a < b) as i64,
+                    BinaryOperator::LessOrEqual => (a <= b) as i64,
+                    BinaryOperator::Greater => (a > b) as i64,
+                    BinaryOperator::GreaterOrEqual => (a >= b) as i64,
+                    BinaryOperator::BitOr => a | b,
+                    BinaryOperator::BitXor => a ^ b,
+                    BinaryOperator::BitAnd => a & b,
+                    BinaryOperator::ShiftLeft => a.wrapping_shl(b as u32),
+                    BinaryOperator::ShiftRight => a.wrapping_shr(b as u32),
+                    BinaryOperator::Add => a.wrapping_add(b),
+                    BinaryOperator::Sub => a.wrapping_sub(b),
+                    BinaryOperator::Mul => a.wrapping_mul(b),
+                    BinaryOperator::Div | BinaryOperator::Rem if b == 0 => {
+                        return Err(String::from("Division by zero"))
+                    }
+                    BinaryOperator::Div => a.wrapping_div(b),
+                    BinaryOperator::Rem => a.wrapping_rem(b),
+                    BinaryOperator::And | BinaryOperator::Or => unreachable!(),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::loader::{vm_running, DATA};
+
+    /// A VM with `$t0 = -1`, `$t1` pointing at the data segment, the word 0x80ff0000 stored
+    /// there and the label `buffer` at the same address.
+    fn sample_vm() -> VM {
+        let mut vm = vm_running(&[]);
+        vm.set_register(8, 0xFFFFFFFF).unwrap();
+        vm.memory.set_word(DATA, 0x80ff0000).unwrap();
+        vm.debug_info.add_symbol("buffer", DATA);
+        vm
+    }
+
+    fn evaluate(vm: &VM, source: &str) -> Result<i64, String> {
+        vm.evaluate(&Expression::parse(source).unwrap(), Some(3))
+    }
+
+    #[test]
+    fn parse_errors() {
+        for source in [
+            "", "1 +", "(1", "word[$t1", "$nope", "1 2", "0xg", "1 @ 2", "é", "1 + €", "$é",
+        ] {
+            assert!(Expression::parse(source).is_err(), "{:?} parsed", source);
+        }
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        let vm = sample_vm();
+
+        for (source, value) in [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("10 - 4 - 3", 3),
+            ("1 << 2 + 1", 8),
+            ("6 & 3 | 8", 10),
+            ("1 | 2 == 3", 1),
+            ("-2 * -3", 6),
+            ("!0 + ~0", 0),
+            ("1 < 2 && 3 > 4 || 5 >= 5", 1),
+            ("7 % 4 / 2", 1),
+        ] {
+            assert_eq!(evaluate(&vm, source), Ok(value), "{}", source);
+        }
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        let vm = sample_vm();
+
+        assert_eq!(evaluate(&vm, "0 && 1 / 0"), Ok(0));
+        assert_eq!(evaluate(&vm, "1 || mem[0]"), Ok(1));
+        assert!(evaluate(&vm, "1 && 1 / 0").is_err());
+        assert!(evaluate(&vm, "0 || mem[0]").is_err());
+    }
+
+    #[test]
+    fn values_are_read_from_the_vm() {
+        let vm = sample_vm();
+
+        for (source, value) in [
+            ("$t0", -1),
+            ("$8 == $t0", 1),
+            ("pc", 0x00400000),
+            ("word[$t1]", 0x80ff0000_u32 as i32 as i64),
+            ("half[$t1]", -0x7f01),
+            ("byte[$t1 + 1]", -1),
+            ("mem[buffer] == word[$t1]", 1),
+            ("buffer", DATA as i64),
+            ("hits", 3),
+        ] {
+            assert_eq!(evaluate(&vm, source), Ok(value), "{}", source);
+        }
+
+        assert!(evaluate(&vm, "missing").is_err());
+        assert!(vm
+            .evaluate(&Expression::parse("hits").unwrap(), None)
+            .is_err());
+    }
+
+    #[test]
+    fn word_sized_numbers_compare_like_registers() {
+        let vm = sample_vm();
+
+        assert_eq!(evaluate(&vm, "$t0 == 0xFFFFFFFF"), Ok(1));
+        assert_eq!(evaluate(&vm, "$t0 == 4294967295"), Ok(1));
+        assert_eq!(evaluate(&vm, "0x7FFFFFFF"), Ok(0x7FFFFFFF));
+        assert_eq!(evaluate(&vm, "0x100000000"), Ok(0x100000000));
+        assert_eq!(
+            evaluate(&vm, "mem[$t1 + 0xFFFFFFFF + 1]"),
+            Ok(0x80ff0000_u32 as i32 as i64)
+        );
+    }
+}
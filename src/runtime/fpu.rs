@@ -0,0 +1,165 @@
+use crate::runtime::errors::{FatalErrorType, RuntimeError};
+
+/// Coprocessor 1, the floating point unit.
+///
+/// The FPU has 32 single precision registers. Double precision values occupy an even-odd pair
+/// of registers, where the even register holds the least significant word.
+#[derive(Clone)]
+pub struct Coprocessor1 {
+    registers: [u32; 32],
+
+    /// The floating point control and status register. Only the condition flags are used.
+    fcsr: u32,
+}
+
+impl Coprocessor1 {
+    pub fn new() -> Coprocessor1 {
+        Coprocessor1 {
+            registers: [0; 32],
+            fcsr: 0,
+        }
+    }
+
+    fn check_register(register: u8) -> Result<(), RuntimeError> {
+        if register > 31 {
+            Err(RuntimeError::new(
+                FatalErrorType::IllegalRegisterAccess,
+                format!("Invalid floating point register number {}", register),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_double_register(register: u8) -> Result<(), RuntimeError> {
+        Self::check_register(register)?;
+
+        if !register.is_multiple_of(2) {
+            Err(RuntimeError::new(
+                FatalErrorType::IllegalRegisterAccess,
+                format!(
+                    "Double precision values must use an even register, not $f{}",
+                    register
+                ),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the raw bits of the given register.
+    pub fn get_register(&self, register: u8) -> Result<u32, RuntimeError> {
+        Self::check_register(register)?;
+
+        Ok(self.registers[register as usize])
+    }
+
+    /// Set the raw bits of the given register.
+    pub fn set_register(&mut self, register: u8, value: u32) -> Result<(), RuntimeError> {
+        Self::check_register(register)?;
+
+        self.registers[register as usize] = value;
+
+        Ok(())
+    }
+
+    pub fn get_float(&self, register: u8) -> Result<f32, RuntimeError> {
+        Ok(f32::from_bits(self.get_register(register)?))
+    }
+
+    pub fn set_float(&mut self, register: u8, value: f32) -> Result<(), RuntimeError> {
+        self.set_register(register, value.to_bits())
+    }
+
+    /// Get the raw bits of the double stored in the given even register and the one after it.
+    pub fn get_double_bits(&self, register: u8) -> Result<u64, RuntimeError> {
+        Self::check_double_register(register)?;
+
+        let low = self.registers[register as usize] as u64;
+        let high = self.registers[register as usize + 1] as u64;
+
+        Ok((high << 32) | low)
+    }
+
+    /// Set the raw bits of the double stored in the given even register and the one after it.
+    pub fn set_double_bits(&mut self, register: u8, value: u64) -> Result<(), RuntimeError> {
+        Self::check_double_register(register)?;
+
+        self.registers[register as usize] = value as u32;
+        self.registers[register as usize + 1] = (value >> 32) as u32;
+
+        Ok(())
+    }
+
+    pub fn get_double(&self, register: u8) -> Result<f64, RuntimeError> {
+        Ok(f64::from_bits(self.get_double_bits(register)?))
+    }
+
+    pub fn set_double(&mut self, register: u8, value: f64) -> Result<(), RuntimeError> {
+        self.set_double_bits(register, value.to_bits())
+    }
+
+    /// Return the bit of the FCSR holding the given condition flag. Flag 0 is bit 23, and
+    /// flags 1 to 7 are bits 25 to 31.
+    fn condition_flag_bit(flag: u8) -> u32 {
+        if flag == 0 {
+            23
+        } else {
+            24 + flag as u32
+        }
+    }
+
+    pub fn get_condition_flag(&self, flag: u8) -> bool {
+        self.fcsr & (1 << Self::condition_flag_bit(flag)) != 0
+    }
+
+    pub fn set_condition_flag(&mut self, flag: u8, value: bool) {
+        let mask = 1 << Self::condition_flag_bit(flag);
+
+        if value {
+            self.fcsr |= mask;
+        } else {
+            self.fcsr &= !mask;
+        }
+    }
+
+    pub fn get_fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    pub fn set_fcsr(&mut self, value: u32) {
+        self.fcsr = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_span_an_even_odd_pair() {
+        let mut fpu = Coprocessor1::new();
+        fpu.set_double(2, 1.5).unwrap();
+
+        assert_eq!(fpu.get_register(2).unwrap(), 0);
+        assert_eq!(fpu.get_register(3).unwrap(), 0x3ff80000);
+        assert_eq!(fpu.get_double(2).unwrap(), 1.5);
+        assert!(fpu.get_double(3).is_err());
+        assert!(fpu.set_double(31, 1.0).is_err());
+    }
+
+    #[test]
+    fn condition_flags_skip_bit_24() {
+        let mut fpu = Coprocessor1::new();
+        fpu.set_condition_flag(0, true);
+        fpu.set_condition_flag(1, true);
+        fpu.set_condition_flag(7, true);
+
+        assert_eq!(fpu.get_fcsr(), (1 << 23) | (1 << 25) | (1 << 31));
+
+        fpu.set_condition_flag(1, false);
+        assert!(fpu.get_condition_flag(0));
+        assert!(!fpu.get_condition_flag(1));
+        assert_eq!(fpu.get_fcsr(), (1 << 23) | (1 << 31));
+    }
+}
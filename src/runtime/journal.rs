@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+
+use crate::mips::instruction::InstructionData;
+use crate::runtime::cp0::Coprocessor0;
+use crate::runtime::errors::RuntimeError;
+use crate::runtime::fpu::Coprocessor1;
+use crate::runtime::stepping::StopReason;
+use crate::runtime::syscalls::SyscallState;
+use crate::runtime::vm::{DelayedLoad, VM};
+
+/// A value overwritten by an instruction, saved so that it can be restored.
+#[derive(Debug, Clone, Copy)]
+pub enum Change {
+    Register(u8, u32),
+    Hi(u32),
+    Lo(u32),
+    Memory(usize, u8),
+}
+
+/// Everything needed to undo one executed instruction.
+struct JournalStep {
+    pc: usize,
+    /// Overwritten values, in the order they were written.
+    changes: Vec<Change>,
+
+    // State that is small enough to copy whole rather than track write by write.
+    fpu: Coprocessor1,
+    cp0: Coprocessor0,
+    syscalls: SyscallState,
+    link: Option<usize>,
+    delayed_branch: Option<usize>,
+    delayed_load: Option<DelayedLoad>,
+
+    /// The instruction, if it was counted in the statistics.
+    counted: Option<InstructionData<'static>>,
+}
+
+/// A bounded history of executed instructions, which lets the VM step backwards.
+pub struct Journal {
+    steps: VecDeque<JournalStep>,
+    /// The step of the instruction being executed, if any.
+    current: Option<JournalStep>,
+}
+
+impl Journal {
+    pub fn new() -> Journal {
+        Journal {
+            steps: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// Record a value about to be overwritten by the instruction being executed. Changes
+    /// made outside of an instruction, such as by the loader, are not recorded.
+    pub fn record(&mut self, change: Change) {
+        if let Some(step) = &mut self.current {
+            step.changes.push(change);
+        }
+    }
+
+    /// The number of instructions that can be undone.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.current = None;
+    }
+}
+
+impl VM {
+    pub fn get_journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Start recording the changes made by the next instruction, if the journal is enabled.
+    pub(super) fn begin_journal_step(&mut self) {
+        if self.options.journal_capacity == 0 {
+            return;
+        }
+
+        self.journal.current = Some(JournalStep {
+            pc: self.get_pc(),
+            changes: vec![],
+            fpu: self.fpu.clone(),
+            cp0: self.cp0.clone(),
+            syscalls: self.save_syscall_state(),
+            link: self.get_link(),
+            delayed_branch: self.get_delayed_branch(),
+            delayed_load: self.get_delayed_load(),
+            counted: None,
+        });
+        self.memory.record_writes();
+    }
+
+    /// Finish recording the instruction started by `begin_journal_step`.
+    pub(super) fn end_journal_step(&mut self) {
+        if let Some(mut step) = self.journal.current.take() {
+            step.changes.extend(
+                self.memory
+                    .take_recorded_writes()
+                    .into_iter()
+                    .map(|(address, value)| Change::Memory(address, value)),
+            );
+
+            self.journal.steps.push_back(step);
+
+            while self.journal.steps.len() > self.options.journal_capacity {
+                self.journal.steps.pop_front();
+            }
+        }
+    }
+
+    /// Count the instruction that was just executed in the statistics, noting in its journal
+    /// step that it was counted.
+    pub(super) fn count_instruction(&mut self, instruction: InstructionData<'static>) {
+        self.statistics.record(&instruction);
+
+        if let Some(step) = self.journal.steps.back_mut() {
+            step.counted = Some(instruction);
+        }
+    }
+
+    /// Undo the most recently executed instruction. Return false if there is nothing left in
+    /// the journal to undo.
+    pub fn step_back(&mut self) -> Result<bool, RuntimeError> {
+        let step = match self.journal.steps.pop_back() {
+            Some(step) => step,
+            None => return Ok(false),
+        };
+
+        // Restore in reverse, so that a location written twice ends up with its original value.
+        for change in step.changes.iter().rev() {
+            match *change {
+                Change::Register(register, value) => self.set_register(register, value)?,
+                Change::Hi(value) => self.set_hi(value),
+                Change::Lo(value) => self.set_lo(value),
+                Change::Memory(address, value) => self.memory.set_byte(address, value)?,
+            }
+        }
+
+        self.set_pc(step.pc);
+        // A run forwards from here continues past a breakpoint that stepping back stopped at.
+        self.stopped_at = Some(step.pc);
+        self.fpu = step.fpu;
+        self.cp0 = step.cp0;
+        self.restore_syscall_state(step.syscalls);
+        self.set_link(step.link);
+        self.set_delayed_branch(step.delayed_branch);
+        self.set_delayed_load(step.delayed_load);
+
+        if let Some(instruction) = step.counted {
+            self.statistics.forget(&instruction);
+        }
+
+        // The undone writes no longer belong to the most recent instruction.
+        self.memory.clear_last_writes();
+        self.clear_last_changes();
+
+        Ok(true)
+    }
+
+    /// Step backwards until the next instruction has a breakpoint, undoing at most
+    /// `max_steps` instructions. Return why stepping stopped, or None if it used up its steps.
+    pub fn run_backward(&mut self, max_steps: usize) -> Result<Option<StopReason>, RuntimeError> {
+        for _ in 0..max_steps {
+            if !self.step_back()? {
+                return Ok(Some(StopReason::StartOfHistory));
+            }
+
+            if self.at_breakpoint_without_hit() {
+                return Ok(Some(StopReason::Breakpoint(self.get_pc())));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::breakpoints::{Breakpoint, BreakpointLocation};
+    use crate::runtime::loader::{vm_running, DATA};
+    use crate::runtime::stepping::{Run, RunTarget};
+
+    /// A VM that has run `addiu $t0, $zero, 5; sw $t0, 0($t1); mult $t0, $t0`, with `$t1`
+    /// pointing at the data segment.
+    fn vm_after_program(journal_capacity: usize) -> VM {
+        let mut vm = vm_running(&[0x24080005, 0xad280000, 0x01080018]);
+        vm.options.journal_capacity = journal_capacity;
+
+        for _ in 0..3 {
+            vm.run(&mut Run::new(RunTarget::Step), 1).unwrap();
+        }
+
+        vm
+    }
+
+    #[test]
+    fn step_back_undoes_each_instruction() {
+        let mut vm = vm_after_program(10);
+        assert_eq!(vm.get_lo(), 25);
+        assert_eq!(vm.statistics.get_total(), 3);
+
+        assert!(vm.step_back().unwrap());
+        assert_eq!(vm.get_lo(), 0);
+        assert_eq!(vm.memory.get_word(DATA).unwrap(), 5);
+        assert_eq!(vm.get_pc(), 0x00400008);
+
+        assert!(vm.step_back().unwrap());
+        assert_eq!(vm.memory.get_word(DATA).unwrap(), 0);
+        assert_eq!(vm.get_register(8).unwrap(), 5);
+
+        assert!(vm.step_back().unwrap());
+        assert_eq!(vm.get_register(8).unwrap(), 0);
+        assert_eq!(vm.get_pc(), 0x00400000);
+        assert_eq!(vm.statistics.get_total(), 0);
+        assert!(vm.statistics.get_mnemonic_counts().is_empty());
+
+        assert!(!vm.step_back().unwrap());
+    }
+
+    #[test]
+    fn journal_keeps_the_latest_instructions() {
+        let mut vm = vm_after_program(2);
+        assert_eq!(vm.get_journal().len(), 2);
+
+        assert!(vm.step_back().unwrap());
+        assert!(vm.step_back().unwrap());
+        assert!(!vm.step_back().unwrap());
+
+        // The first instruction fell out of the journal, so its result stays.
+        assert_eq!(vm.get_register(8).unwrap(), 5);
+        assert_eq!(vm.get_pc(), 0x00400004);
+        assert_eq!(vm.statistics.get_total(), 1);
+    }
+
+    #[test]
+    fn run_backward_stops_at_breakpoints() {
+        let mut vm = vm_after_program(10);
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400004)));
+
+        assert!(matches!(
+            vm.run_backward(10),
+            Ok(Some(StopReason::Breakpoint(0x00400004)))
+        ));
+        assert!(matches!(
+            vm.run_backward(10),
+            Ok(Some(StopReason::StartOfHistory))
+        ));
+        assert_eq!(vm.get_pc(), 0x00400000);
+        assert_eq!(vm.get_breakpoints()[0].hits, 0);
+    }
+
+    #[test]
+    fn disabled_journal_cannot_step_back() {
+        let mut vm = vm_after_program(0);
+
+        assert!(vm.get_journal().is_empty());
+        assert!(!vm.step_back().unwrap());
+        assert_eq!(vm.get_lo(), 25);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::runtime::errors::{FatalErrorType, RuntimeError};
+use crate::runtime::memory::SegmentDirection;
+use crate::runtime::vm::{MemoryLayout, VM};
+
+/// Load a raw memory image of the text segment, as produced by MARS's "dump memory" in
+/// binary format, and point the program counter at its first instruction.
+pub fn load_binary(vm: &mut VM, program: &[u8]) -> Result<(), RuntimeError> {
+    let text = vm.memory.mut_segment_by_name("text").ok_or_else(|| {
+        RuntimeError::new(
+            FatalErrorType::IllegalMemoryAccess,
+            String::from("Cannot load a program without a text segment"),
+        )
+    })?;
+    let text_low = text.get_low_address();
+
+    if program.len() > text.get_size() {
+        return Err(RuntimeError::new(
+            FatalErrorType::IllegalMemoryAccess,
+            format!(
+                "Program of {} bytes does not fit in the text segment of {} bytes",
+                program.len(),
+                text.get_size()
+            ),
+        ));
+    }
+
+    // The image is already in memory order, so it is copied byte by byte regardless of the
+    // endianness of the VM.
+    text.allow_writes();
+    for (i, byte) in program.iter().enumerate() {
+        text.set_byte(text_low + i, *byte)?;
+    }
+    text.set_read_only();
+
+    vm.set_pc(text_low);
+
+    Ok(())
+}
+
+/// The label execution starts at, if the program defines it.
+const ENTRY_LABEL: &str = "main";
+
+/// Set up the registers and stack the way MARS does before a program starts. `$gp` points
+/// 0x8000 bytes into the data segment, `$sp` to the top of the stack, and the PC to the
+/// `main` label if there is one.
+///
+/// Program arguments are copied onto the top of the stack as NUL-terminated strings, followed
+/// by a NULL-terminated argv array and argc, which `$sp` then points at. `$a0` holds argc and
+/// `$a1` the address of argv.
+pub fn initialize(vm: &mut VM, arguments: &[String]) -> Result<(), RuntimeError> {
+    let segment_bounds = |name| {
+        vm.memory
+            .segment_by_name(name)
+            .map(|segment| (segment.get_low_address(), segment.get_high_address()))
+            .ok_or_else(|| {
+                RuntimeError::new(
+                    FatalErrorType::IllegalMemoryAccess,
+                    format!("Cannot initialize a program without a {} segment", name),
+                )
+            })
+    };
+
+    let (data_low, _) = segment_bounds("data")?;
+    let (_, stack_high) = segment_bounds("stack")?;
+
+    vm.set_gp((data_low + 0x8000) as u32);
+
+    // The first word-aligned address above the stack.
+    let top = (stack_high + 1) & !3;
+
+    if arguments.is_empty() {
+        vm.set_sp((top - 4) as u32);
+    } else {
+        let mut address = top;
+        let mut argv = vec![];
+
+        for argument in arguments {
+            address -= argument.len() + 1;
+            argv.push(address as u32);
+
+            for (i, byte) in argument.bytes().chain(std::iter::once(0)).enumerate() {
+                vm.memory.set_byte(address + i, byte)?;
+            }
+        }
+
+        // Below the strings come argv, with a NULL pointer after the last argument, and argc.
+        address &= !3;
+        argv.push(0);
+        address -= argv.len() * 4;
+
+        for (i, pointer) in argv.iter().enumerate() {
+            vm.memory.set_word(address + i * 4, *pointer)?;
+        }
+
+        address -= 4;
+        vm.memory.set_word(address, arguments.len() as u32)?;
+
+        vm.set_sp(address as u32);
+        vm.set_a0(arguments.len() as u32);
+        vm.set_a1((address + 4) as u32);
+    }
+
+    if let Some(entry) = vm.debug_info.symbol_address(ENTRY_LABEL) {
+        vm.set_pc(entry);
+    }
+
+    // The initial values are not changes made by the program.
+    vm.clear_last_changes();
+
+    Ok(())
+}
+
+/// The program to load into the VM, kept so that it can be reloaded on reset.
+pub struct Program {
+    pub image: Image,
+    /// The arguments passed to the program in argc and argv.
+    pub arguments: Vec<String>,
+}
+
+pub enum Image {
+    /// A raw memory image for the text segment.
+    Binary(Vec<u8>),
+    Demo,
+}
+
+/// A VM with the memory layout programs are loaded into.
+pub fn new_vm() -> VM {
+    VM::new(MemoryLayout::mars(0x1000, 0x1000))
+}
+
+/// Load `program` and set up its registers and stack to start running.
+pub fn load_program(vm: &mut VM, program: &Program) -> Result<(), RuntimeError> {
+    match &program.image {
+        Image::Binary(bytes) => load_binary(vm, bytes)?,
+        Image::Demo => load_demo(vm),
+    }
+
+    initialize(vm, &program.arguments)
+}
+
+/// Restore the VM to the state right after the program was loaded, keeping its options,
+/// breakpoints and watchpoints.
+pub fn reset(vm: &mut VM, program: &Program) -> Result<(), RuntimeError> {
+    let mut fresh = new_vm();
+    // The program is loaded in the same byte order, and with the symbols that give its entry
+    // point.
+    fresh.memory.set_endianness(vm.memory.get_endianness());
+    fresh.debug_info = vm.debug_info.clone();
+    load_program(&mut fresh, program)?;
+
+    let previous = std::mem::replace(vm, fresh);
+    vm.keep_debugging_state(previous);
+
+    Ok(())
+}
+
+/// Load a short demo program, for when no program is given.
+fn load_demo(vm: &mut VM) {
+    let text_segment = vm.memory.mut_segment_by_name("text").unwrap();
+    text_segment.allow_writes();
+
+    {
+        let text_segment = vm.memory.segment_by_name("text").unwrap();
+
+        let text_ptr =
+            vm.memory
+                .align_address(text_segment.get_low_address(), 4, SegmentDirection::Up);
+
+        vm.memory.set_word(text_ptr, 0x2409ffff).unwrap();
+        vm.memory.set_word(text_ptr + 4, 0x240a0002).unwrap();
+        vm.memory.set_word(text_ptr + 8, 0x012a0018).unwrap();
+        vm.set_pc(text_ptr);
+    }
+}
+
+/// Load a program given as instruction words, for tests.
+#[cfg(test)]
+pub fn load_words(vm: &mut VM, words: &[u32]) {
+    use crate::runtime::memory::Endianness;
+
+    let program: Vec<u8> = words
+        .iter()
+        .flat_map(|word| match vm.memory.get_endianness() {
+            Endianness::Big => word.to_be_bytes(),
+            Endianness::Little => word.to_le_bytes(),
+        })
+        .collect();
+
+    load_binary(vm, &program).unwrap();
+}
+
+/// The start of the data segment in the layout used by `vm_running`, for tests.
+#[cfg(test)]
+pub const DATA: usize = 0x10010000;
+
+/// A VM about to run `words`, with `$t1` pointing at the data segment, for tests.
+#[cfg(test)]
+pub fn vm_running(words: &[u32]) -> VM {
+    let mut vm = new_vm();
+    load_words(&mut vm, words);
+    vm.set_register(9, DATA as u32).unwrap();
+    vm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::breakpoints::{Breakpoint, BreakpointLocation};
+    use crate::runtime::debug_info::DebugInfo;
+    use crate::runtime::memory::Endianness;
+    use crate::runtime::stepping::{Run, RunTarget};
+
+    fn read_string(vm: &VM, address: usize) -> String {
+        (address..)
+            .map(|address| vm.memory.get_byte(address).unwrap())
+            .take_while(|byte| *byte != 0)
+            .map(char::from)
+            .collect()
+    }
+
+    #[test]
+    fn initialize_starts_at_main_with_arguments() {
+        let mut vm = new_vm();
+        vm.debug_info = DebugInfo::parse("symbol main 0x00400008").unwrap();
+        load_words(&mut vm, &[0, 0, 0]);
+
+        let arguments = [String::from("one"), String::from("two")];
+        initialize(&mut vm, &arguments).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x00400008);
+        assert_eq!(vm.get_gp(), 0x10018000);
+        assert_eq!(vm.get_a0(), 2);
+        assert_eq!(vm.memory.get_word(vm.get_sp() as usize).unwrap(), 2);
+        assert_eq!(vm.get_a1(), vm.get_sp() + 4);
+
+        let argv = vm.get_a1() as usize;
+        for (i, argument) in arguments.iter().enumerate() {
+            let pointer = vm.memory.get_word(argv + i * 4).unwrap() as usize;
+            assert_eq!(read_string(&vm, pointer), *argument);
+        }
+        assert_eq!(vm.memory.get_word(argv + 8).unwrap(), 0);
+    }
+
+    #[test]
+    fn initialize_without_main_starts_at_the_text_segment() {
+        let mut vm = new_vm();
+        load_words(&mut vm, &[0]);
+        initialize(&mut vm, &[]).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x00400000);
+        assert_eq!(vm.get_sp(), 0x7FFFFFFC);
+        assert_eq!(vm.get_a0(), 0);
+    }
+
+    /// `addiu $v0, $zero, 5; syscall`, which reads an integer.
+    fn read_int_program() -> Program {
+        Program {
+            image: Image::Binary(vec![0x24, 0x02, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0c]),
+            arguments: vec![],
+        }
+    }
+
+    #[test]
+    fn reset_restores_the_loaded_program() {
+        let program = read_int_program();
+        let mut vm = new_vm();
+        load_program(&mut vm, &program).unwrap();
+
+        vm.run(&mut Run::new(RunTarget::Step), 1).unwrap();
+        vm.set_register(8, 1).unwrap();
+        vm.memory.set_word(DATA, 2).unwrap();
+        vm.fpu.set_float(2, 1.5).unwrap();
+        vm.fpu.set_fcsr(1 << 23);
+        vm.push_input("7\n");
+
+        reset(&mut vm, &program).unwrap();
+
+        assert_eq!(vm.get_pc(), 0x00400000);
+        assert_eq!(vm.get_register(2).unwrap(), 0);
+        assert_eq!(vm.get_register(8).unwrap(), 0);
+        assert_eq!(vm.get_sp(), 0x7FFFFFFC);
+        assert_eq!(vm.memory.get_word(DATA).unwrap(), 0);
+        assert_eq!(vm.fpu.get_float(2).unwrap(), 0.0);
+        assert_eq!(vm.fpu.get_fcsr(), 0);
+
+        // The input given before the reset is gone, so the read waits for more.
+        for _ in 0..2 {
+            vm.run(&mut Run::new(RunTarget::Step), 1).unwrap();
+        }
+        assert!(vm.get_input_request().is_some());
+    }
+
+    #[test]
+    fn reset_keeps_breakpoints_symbols_and_byte_order() {
+        let program = read_int_program();
+        let mut vm = new_vm();
+        vm.debug_info = DebugInfo::parse("symbol main 0x00400004").unwrap();
+        load_program(&mut vm, &program).unwrap();
+
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400004)));
+        vm.memory.set_endianness(Endianness::Little);
+
+        reset(&mut vm, &program).unwrap();
+
+        assert_eq!(vm.get_breakpoints().len(), 1);
+        assert_eq!(vm.debug_info.symbol_address("main"), Some(0x00400004));
+        assert_eq!(vm.get_pc(), 0x00400004);
+        assert_eq!(vm.memory.get_endianness(), Endianness::Little);
+    }
+}
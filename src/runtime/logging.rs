@@ -1,9 +1,7 @@
+use crate::runtime::errors::{FatalErrorType, RuntimeError, Trap, Warning};
 use colored::*;
-use crate::runtime::errors::{RuntimeError, FatalErrorType, Trap};
 
-pub struct Logger {
-    
-}
+pub struct Logger {}
 
 impl Logger {
     /// Log a fatal error.
@@ -15,6 +13,7 @@ impl Logger {
                 FatalErrorType::IllegalMemoryAccess => "ILLEGAL_MEMORY_ACCESS",
                 FatalErrorType::IllegalInstruction => "ILLEGAL_INSTRUCTION",
                 FatalErrorType::IllegalRegisterAccess => "ILLEGAL_REGISTER",
+                FatalErrorType::InvalidSyscall => "INVALID_SYSCALL",
             },
             err.message
         );
@@ -24,4 +23,19 @@ impl Logger {
     pub fn trap_error(&self, trap: &Trap) {
         eprintln!("{} {}", "[trap]".red().bold(), trap.message);
     }
+
+    /// Log a breakpoint that was reached.
+    pub fn breakpoint(&self, address: usize) {
+        eprintln!("{} {:#010x}", "[breakpoint]".yellow().bold(), address);
+    }
+
+    /// Log a watchpoint hit.
+    pub fn watchpoint(&self, message: &str) {
+        eprintln!("{} {}", "[watchpoint]".yellow().bold(), message);
+    }
+
+    /// Log a warning.
+    pub fn warning(&self, warning: &Warning) {
+        eprintln!("{} {}", "[warning]".yellow().bold(), warning.message);
+    }
 }
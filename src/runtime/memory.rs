@@ -1,10 +1,20 @@
 use crate::runtime::errors::{FatalErrorType, RuntimeError};
+use crate::runtime::watchpoints::{WatchAccess, Watchpoints};
 
 pub enum SegmentDirection {
     Up,
     Down,
 }
 
+/// The order in which the bytes of a halfword or word are stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    /// The most significant byte is stored at the lowest address.
+    Big,
+    /// The least significant byte is stored at the lowest address, as in MARS.
+    Little,
+}
+
 /// A representation of a segment of the MIPS memory layout.
 ///
 /// MIPS may address up to 4GiB of memory, but allocating that much memory is often not
@@ -55,7 +65,7 @@ impl MemorySegment {
                 true => vec![0; size],
                 false => Vec::new(),
             },
-            read_only: read_only,
+            read_only,
         }
     }
 
@@ -99,20 +109,57 @@ impl MemorySegment {
         }
     }
 
-    pub fn get_halfword(&self, address: usize) -> Result<u16, RuntimeError> {
-        let hi = self.get_byte(address)? as u16;
-        let lo = self.get_byte(address + 1)? as u16;
+    /// Get the halfword at the given address, stored with the given byte order.
+    ///
+    /// If the address is not aligned to a halfword boundary, return an error.
+    pub fn get_halfword(
+        &self,
+        address: usize,
+        endianness: Endianness,
+    ) -> Result<u16, RuntimeError> {
+        if !address.is_multiple_of(2) {
+            return Err(RuntimeError::new(
+                FatalErrorType::IllegalMemoryAccess,
+                format!(
+                    "Attempted to read halfword from unaligned address {:#010x}",
+                    address
+                ),
+            ));
+        }
 
-        Ok((hi << 8) | lo)
+        let bytes = [self.get_byte(address)?, self.get_byte(address + 1)?];
+
+        Ok(match endianness {
+            Endianness::Big => u16::from_be_bytes(bytes),
+            Endianness::Little => u16::from_le_bytes(bytes),
+        })
     }
 
-    pub fn get_word(&self, address: usize) -> Result<u32, RuntimeError> {
-        let b1 = self.get_byte(address)? as u32;
-        let b2 = self.get_byte(address + 1)? as u32;
-        let b3 = self.get_byte(address + 2)? as u32;
-        let b4 = self.get_byte(address + 3)? as u32;
+    /// Get the word at the given address, stored with the given byte order.
+    ///
+    /// If the address is not aligned to a word boundary, return an error.
+    pub fn get_word(&self, address: usize, endianness: Endianness) -> Result<u32, RuntimeError> {
+        if !address.is_multiple_of(4) {
+            return Err(RuntimeError::new(
+                FatalErrorType::IllegalMemoryAccess,
+                format!(
+                    "Attempted to read word from unaligned address {:#010x}",
+                    address
+                ),
+            ));
+        }
 
-        Ok((b1 << 24) | (b2 << 16) | (b3 << 8) | b4)
+        let bytes = [
+            self.get_byte(address)?,
+            self.get_byte(address + 1)?,
+            self.get_byte(address + 2)?,
+            self.get_byte(address + 3)?,
+        ];
+
+        Ok(match endianness {
+            Endianness::Big => u32::from_be_bytes(bytes),
+            Endianness::Little => u32::from_le_bytes(bytes),
+        })
     }
 
     /// Set the byte at the given address to the given value.
@@ -147,11 +194,17 @@ impl MemorySegment {
         Ok(())
     }
 
-    /// Set the halfword at the given address to the given value.
+    /// Set the halfword at the given address to the given value, stored with the given byte
+    /// order.
     ///
     /// If the address is not aligned to a halfword boundary, return an error.
-    pub fn set_halfword(&mut self, address: usize, value: u16) -> Result<(), RuntimeError> {
-        if address % 2 != 0 {
+    pub fn set_halfword(
+        &mut self,
+        address: usize,
+        value: u16,
+        endianness: Endianness,
+    ) -> Result<(), RuntimeError> {
+        if !address.is_multiple_of(2) {
             Err(RuntimeError::new(
                 FatalErrorType::IllegalMemoryAccess,
                 format!(
@@ -160,21 +213,28 @@ impl MemorySegment {
                 ),
             ))
         } else {
-            let hi = (value >> 8) as u8;
-            let lo = value as u8;
+            let bytes = match endianness {
+                Endianness::Big => value.to_be_bytes(),
+                Endianness::Little => value.to_le_bytes(),
+            };
 
-            self.set_byte(address, hi)?;
-            self.set_byte(address + 1, lo)?;
+            self.set_byte(address, bytes[0])?;
+            self.set_byte(address + 1, bytes[1])?;
 
             Ok(())
         }
     }
 
-    /// Set the word at the given address to the given value.
+    /// Set the word at the given address to the given value, stored with the given byte order.
     ///
     /// If the address is not aligned to a word boundary, throw a fatal error.
-    pub fn set_word(&mut self, address: usize, value: u32) -> Result<(), RuntimeError> {
-        if address % 4 != 0 {
+    pub fn set_word(
+        &mut self,
+        address: usize,
+        value: u32,
+        endianness: Endianness,
+    ) -> Result<(), RuntimeError> {
+        if !address.is_multiple_of(4) {
             Err(RuntimeError::new(
                 FatalErrorType::IllegalMemoryAccess,
                 format!(
@@ -183,15 +243,14 @@ impl MemorySegment {
                 ),
             ))
         } else {
-            let b1 = (value >> 24) as u8;
-            let b2 = (value >> 16) as u8;
-            let b3 = (value >> 8) as u8;
-            let b4 = value as u8;
+            let bytes = match endianness {
+                Endianness::Big => value.to_be_bytes(),
+                Endianness::Little => value.to_le_bytes(),
+            };
 
-            self.set_byte(address, b1)?;
-            self.set_byte(address + 1, b2)?;
-            self.set_byte(address + 2, b3)?;
-            self.set_byte(address + 3, b4)?;
+            for (i, byte) in bytes.iter().enumerate() {
+                self.set_byte(address + i, *byte)?;
+            }
 
             Ok(())
         }
@@ -225,11 +284,110 @@ impl MemorySegment {
 /// A memory map is a collection of segments.
 pub struct MemoryMap {
     segments: Vec<MemorySegment>,
+    endianness: Endianness,
+
+    /// The original value of each byte overwritten while writes are being recorded, as
+    /// (address, value) pairs in the order they were written.
+    recorded_writes: Option<Vec<(usize, u8)>>,
+
+    /// The writes made by the executing or most recently executed instruction, as
+    /// (address, size) pairs.
+    last_writes: Vec<(usize, usize)>,
+
+    pub watchpoints: Watchpoints,
 }
 
 impl MemoryMap {
     pub fn new() -> Self {
-        Self { segments: vec![] }
+        Self {
+            segments: vec![],
+            endianness: Endianness::Big,
+            recorded_writes: None,
+            last_writes: vec![],
+            watchpoints: Watchpoints::new(),
+        }
+    }
+
+    /// Start recording the original values of bytes that are overwritten.
+    pub fn record_writes(&mut self) {
+        self.recorded_writes = Some(vec![]);
+    }
+
+    /// Stop recording writes, and return the original values of the bytes overwritten since
+    /// `record_writes` was called.
+    pub fn take_recorded_writes(&mut self) -> Vec<(usize, u8)> {
+        self.recorded_writes.take().unwrap_or_default()
+    }
+
+    pub fn get_last_writes(&self) -> &Vec<(usize, usize)> {
+        &self.last_writes
+    }
+
+    pub fn clear_last_writes(&mut self) {
+        self.last_writes.clear();
+    }
+
+    /// Return true if the most recently executed instruction wrote the byte at `address`.
+    pub fn was_last_written(&self, address: usize) -> bool {
+        self.last_writes
+            .iter()
+            .any(|&(low, size)| (low..low + size).contains(&address))
+    }
+
+    /// Get the bytes from `address` to `address + size` without checking watchpoints, as
+    /// (address, value) pairs. Bytes that cannot be read are left out.
+    fn peek_bytes(&self, address: usize, size: usize) -> Vec<(usize, u8)> {
+        (address..address + size)
+            .filter_map(|a| {
+                self.get_segment(a)
+                    .and_then(|segment| segment.get_byte(a).ok())
+                    .map(|byte| (a, byte))
+            })
+            .collect()
+    }
+
+    /// Perform a write of `size` bytes at `address`. This records the bytes it overwrites if
+    /// writes are being recorded, checks write and change watchpoints, and remembers the
+    /// write for `get_last_writes`.
+    fn recorded<F>(&mut self, address: usize, size: usize, write: F) -> Result<(), RuntimeError>
+    where
+        F: FnOnce(&mut MemoryMap) -> Result<(), RuntimeError>,
+    {
+        let watch_changes = self.watchpoints.watches_changes(address, size);
+
+        let original = if self.recorded_writes.is_some() || watch_changes {
+            self.peek_bytes(address, size)
+        } else {
+            vec![]
+        };
+
+        write(self)?;
+
+        self.watchpoints.check(WatchAccess::Write, address, size);
+        if watch_changes && self.peek_bytes(address, size) != original {
+            self.watchpoints.check(WatchAccess::Change, address, size);
+        }
+
+        if let Some(recorded) = &mut self.recorded_writes {
+            recorded.extend(original);
+        }
+
+        // As with watchpoints, only writes made by an instruction count.
+        if self.watchpoints.is_armed() {
+            self.last_writes.push((address, size));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    /// Set the byte order used for halfword and word accesses. This does not change the
+    /// contents of memory.
+    pub fn set_endianness(&mut self, endianness: Endianness) {
+        self.endianness = endianness;
     }
 
     pub fn add_segment(&mut self, segment: MemorySegment) {
@@ -239,7 +397,7 @@ impl MemoryMap {
 
         for segment in &self.segments {
             for other_segment in &self.segments {
-                if segment as *const MemorySegment == other_segment as *const MemorySegment {
+                if std::ptr::eq(segment, other_segment) {
                     continue;
                 }
 
@@ -256,26 +414,20 @@ impl MemoryMap {
     }
 
     pub fn get_segment(&self, address: usize) -> Option<&MemorySegment> {
-        for segment in &self.segments {
-            if address >= segment.get_low_address() && address <= segment.get_high_address() {
-                return Some(segment);
-            }
-        }
-
-        None
+        self.segments.iter().find(|segment| {
+            address >= segment.get_low_address() && address <= segment.get_high_address()
+        })
     }
 
     pub fn get_segment_mut(&mut self, address: usize) -> Option<&mut MemorySegment> {
-        for segment in &mut self.segments {
-            if address >= segment.get_low_address() && address <= segment.get_high_address() {
-                return Some(segment);
-            }
-        }
-
-        None
+        self.segments.iter_mut().find(|segment| {
+            address >= segment.get_low_address() && address <= segment.get_high_address()
+        })
     }
 
     pub fn get_byte(&self, address: usize) -> Result<u8, RuntimeError> {
+        self.watchpoints.check(WatchAccess::Read, address, 1);
+
         if let Some(segment) = self.get_segment(address) {
             segment.get_byte(address)
         } else {
@@ -284,67 +436,213 @@ impl MemoryMap {
     }
 
     pub fn get_halfword(&self, address: usize) -> Result<u16, RuntimeError> {
+        self.watchpoints.check(WatchAccess::Read, address, 2);
+
         if let Some(segment) = self.get_segment(address) {
-            segment.get_halfword(address)
+            segment.get_halfword(address, self.endianness)
         } else {
             Err(RuntimeError::err_invalid_read(address))
         }
     }
 
     pub fn get_word(&self, address: usize) -> Result<u32, RuntimeError> {
+        self.watchpoints.check(WatchAccess::Read, address, 4);
+
         if let Some(segment) = self.get_segment(address) {
-            segment.get_word(address)
+            segment.get_word(address, self.endianness)
         } else {
             Err(RuntimeError::err_invalid_read(address))
         }
     }
 
     pub fn set_byte(&mut self, address: usize, value: u8) -> Result<(), RuntimeError> {
-        if let Some(segment) = self.get_segment_mut(address) {
-            segment.set_byte(address, value)
-        } else {
-            Err(RuntimeError::err_invalid_write(address))
-        }
+        self.recorded(address, 1, |memory| {
+            if let Some(segment) = memory.get_segment_mut(address) {
+                segment.set_byte(address, value)
+            } else {
+                Err(RuntimeError::err_invalid_write(address))
+            }
+        })
     }
 
     pub fn set_halfword(&mut self, address: usize, value: u16) -> Result<(), RuntimeError> {
-        if let Some(segment) = self.get_segment_mut(address) {
-            segment.set_halfword(address, value)
-        } else {
-            Err(RuntimeError::err_invalid_write(address))
-        }
+        let endianness = self.endianness;
+
+        self.recorded(address, 2, |memory| {
+            if let Some(segment) = memory.get_segment_mut(address) {
+                segment.set_halfword(address, value, endianness)
+            } else {
+                Err(RuntimeError::err_invalid_write(address))
+            }
+        })
     }
 
     pub fn set_word(&mut self, address: usize, value: u32) -> Result<(), RuntimeError> {
-        if let Some(segment) = self.get_segment_mut(address) {
-            segment.set_word(address, value)
-        } else {
-            Err(RuntimeError::err_invalid_write(address))
+        let endianness = self.endianness;
+
+        self.recorded(address, 4, |memory| {
+            if let Some(segment) = memory.get_segment_mut(address) {
+                segment.set_word(address, value, endianness)
+            } else {
+                Err(RuntimeError::err_invalid_write(address))
+            }
+        })
+    }
+
+    /// Get the doubleword at the given address, as used by double precision loads.
+    ///
+    /// If the address is not aligned to a doubleword boundary, return an error.
+    pub fn get_doubleword(&self, address: usize) -> Result<u64, RuntimeError> {
+        if !address.is_multiple_of(8) {
+            return Err(RuntimeError::new(
+                FatalErrorType::IllegalMemoryAccess,
+                format!(
+                    "Attempted to read doubleword from unaligned address {:#010x}",
+                    address
+                ),
+            ));
         }
+
+        let first = self.get_word(address)? as u64;
+        let second = self.get_word(address + 4)? as u64;
+
+        Ok(match self.endianness {
+            Endianness::Big => (first << 32) | second,
+            Endianness::Little => (second << 32) | first,
+        })
     }
 
-    pub fn get_segments(&self) -> &Vec<MemorySegment> {
-        &self.segments
+    /// Set the doubleword at the given address to the given value.
+    ///
+    /// If the address is not aligned to a doubleword boundary, return an error.
+    pub fn set_doubleword(&mut self, address: usize, value: u64) -> Result<(), RuntimeError> {
+        if !address.is_multiple_of(8) {
+            return Err(RuntimeError::new(
+                FatalErrorType::IllegalMemoryAccess,
+                format!(
+                    "Attempted to write doubleword to unaligned address {:#010x}",
+                    address
+                ),
+            ));
+        }
+
+        let (first, second) = match self.endianness {
+            Endianness::Big => ((value >> 32) as u32, value as u32),
+            Endianness::Little => (value as u32, (value >> 32) as u32),
+        };
+
+        self.set_word(address, first)?;
+        self.set_word(address + 4, second)
     }
 
-    pub fn segment_by_name(&self, name: &str) -> Option<&MemorySegment> {
-        for segment in &self.segments {
-            if segment.name == name {
-                return Some(segment);
-            }
+    /// Return how far `address` is from the most significant byte of its word, in bytes.
+    fn significance_offset(&self, address: usize) -> u32 {
+        match self.endianness {
+            Endianness::Big => (address % 4) as u32,
+            Endianness::Little => 3 - (address % 4) as u32,
         }
+    }
+
+    /// Return the (address, size) of the bytes between `address` and the least significant
+    /// byte of its word (`left`), or the most significant byte (otherwise). These are the
+    /// bytes accessed by `lwl`/`swl` and `lwr`/`swr`.
+    fn partial_word(&self, address: usize, left: bool) -> (usize, usize) {
+        let aligned = address - address % 4;
 
-        None
+        match (self.endianness, left) {
+            (Endianness::Big, true) | (Endianness::Little, false) => (address, 4 - address % 4),
+            (Endianness::Big, false) | (Endianness::Little, true) => (aligned, address % 4 + 1),
+        }
     }
 
-    pub fn mut_segment_by_name(&mut self, name: &str) -> Option<&mut MemorySegment> {
-        for segment in &mut self.segments {
-            if segment.name == name {
-                return Some(segment);
-            }
+    /// Get the word at the aligned `address` without checking watchpoints.
+    fn peek_word(&self, address: usize) -> Result<u32, RuntimeError> {
+        match self.get_segment(address) {
+            Some(segment) => segment.get_word(address, self.endianness),
+            None => Err(RuntimeError::err_invalid_read(address)),
         }
+    }
+
+    /// Load the bytes from `address` up to the least significant byte of its word into the
+    /// most significant bytes of `value`, keeping the rest of `value`. This is the `lwl`
+    /// instruction.
+    pub fn get_word_left(&self, address: usize, value: u32) -> Result<u32, RuntimeError> {
+        let (low, size) = self.partial_word(address, true);
+        self.watchpoints.check(WatchAccess::Read, low, size);
+
+        let word = self.peek_word(address - address % 4)?;
+        let shift = 8 * self.significance_offset(address);
+
+        Ok((word << shift) | (value & !(u32::MAX << shift)))
+    }
+
+    /// Load the bytes from the most significant byte of the word containing `address` up to
+    /// `address` into the least significant bytes of `value`, keeping the rest of `value`.
+    /// This is the `lwr` instruction.
+    pub fn get_word_right(&self, address: usize, value: u32) -> Result<u32, RuntimeError> {
+        let (low, size) = self.partial_word(address, false);
+        self.watchpoints.check(WatchAccess::Read, low, size);
+
+        let word = self.peek_word(address - address % 4)?;
+        let shift = 8 * (3 - self.significance_offset(address));
+
+        Ok((word >> shift) | (value & !(u32::MAX >> shift)))
+    }
+
+    /// Store the most significant bytes of `value` from `address` up to the least significant
+    /// byte of its word. This is the `swl` instruction.
+    pub fn set_word_left(&mut self, address: usize, value: u32) -> Result<(), RuntimeError> {
+        let aligned = address - address % 4;
+        let word = self.peek_word(aligned)?;
+        let shift = 8 * self.significance_offset(address);
+
+        let (low, size) = self.partial_word(address, true);
+        self.set_partial_word(low, size, (word & !(u32::MAX >> shift)) | (value >> shift))
+    }
+
+    /// Store the least significant bytes of `value` from the most significant byte of the
+    /// word containing `address` up to `address`. This is the `swr` instruction.
+    pub fn set_word_right(&mut self, address: usize, value: u32) -> Result<(), RuntimeError> {
+        let aligned = address - address % 4;
+        let word = self.peek_word(aligned)?;
+        let shift = 8 * (3 - self.significance_offset(address));
+
+        let (low, size) = self.partial_word(address, false);
+        self.set_partial_word(low, size, (word & !(u32::MAX << shift)) | (value << shift))
+    }
 
-        None
+    /// Write `word` to the word containing `address`, where only the `size` bytes from
+    /// `address` differ from what is already there. Only those bytes count as written.
+    fn set_partial_word(
+        &mut self,
+        address: usize,
+        size: usize,
+        word: u32,
+    ) -> Result<(), RuntimeError> {
+        let aligned = address - address % 4;
+        let endianness = self.endianness;
+
+        self.recorded(address, size, |memory| {
+            if let Some(segment) = memory.get_segment_mut(aligned) {
+                segment.set_word(aligned, word, endianness)
+            } else {
+                Err(RuntimeError::err_invalid_write(aligned))
+            }
+        })
+    }
+
+    pub fn get_segments(&self) -> &Vec<MemorySegment> {
+        &self.segments
+    }
+
+    pub fn segment_by_name(&self, name: &str) -> Option<&MemorySegment> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    pub fn mut_segment_by_name(&mut self, name: &str) -> Option<&mut MemorySegment> {
+        self.segments
+            .iter_mut()
+            .find(|segment| segment.name == name)
     }
 
     /// Return the first address that is aligned to the given alignment, starting from `address`,
@@ -357,7 +655,7 @@ impl MemoryMap {
     ) -> usize {
         let mut address = address;
 
-        while address % alignment as usize != 0 {
+        while !address.is_multiple_of(alignment as usize) {
             match direction {
                 SegmentDirection::Up => {
                     address += 1;
@@ -371,3 +669,179 @@ impl MemoryMap {
         address
     }
 }
+
+/// Parse an address, in hexadecimal with a "0x" prefix or otherwise in decimal.
+pub fn parse_address(text: &str) -> Result<usize, String> {
+    let result = match text.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+
+    result.map_err(|_| format!("Invalid address \"{}\"", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::loader::{vm_running, DATA};
+    use crate::runtime::watchpoints::Watchpoint;
+
+    /// A memory map with the bytes 11 22 33 44 at the start of the data segment.
+    fn sample_memory(endianness: Endianness) -> MemoryMap {
+        let mut memory = vm_running(&[]).memory;
+        memory.set_endianness(endianness);
+
+        for (i, byte) in [0x11, 0x22, 0x33, 0x44].into_iter().enumerate() {
+            memory.set_byte(DATA + i, byte).unwrap();
+        }
+
+        memory
+    }
+
+    fn bytes(memory: &MemoryMap) -> [u8; 4] {
+        [0, 1, 2, 3].map(|i| memory.get_byte(DATA + i).unwrap())
+    }
+
+    #[test]
+    fn lwl_and_lwr_big_endian() {
+        let memory = sample_memory(Endianness::Big);
+        let left = [0x11223344, 0x223344dd, 0x3344ccdd, 0x44bbccdd];
+        let right = [0xaabbcc11, 0xaabb1122, 0xaa112233, 0x11223344];
+
+        for offset in 0..4 {
+            let address = DATA + offset;
+            assert_eq!(
+                memory.get_word_left(address, 0xaabbccdd).unwrap(),
+                left[offset]
+            );
+            assert_eq!(
+                memory.get_word_right(address, 0xaabbccdd).unwrap(),
+                right[offset]
+            );
+        }
+    }
+
+    #[test]
+    fn lwl_and_lwr_little_endian() {
+        let memory = sample_memory(Endianness::Little);
+        let left = [0x11bbccdd, 0x2211ccdd, 0x332211dd, 0x44332211];
+        let right = [0x44332211, 0xaa443322, 0xaabb4433, 0xaabbcc44];
+
+        for offset in 0..4 {
+            let address = DATA + offset;
+            assert_eq!(
+                memory.get_word_left(address, 0xaabbccdd).unwrap(),
+                left[offset]
+            );
+            assert_eq!(
+                memory.get_word_right(address, 0xaabbccdd).unwrap(),
+                right[offset]
+            );
+        }
+    }
+
+    #[test]
+    fn swl_and_swr_big_endian() {
+        let left = [
+            [0xaa, 0xbb, 0xcc, 0xdd],
+            [0x11, 0xaa, 0xbb, 0xcc],
+            [0x11, 0x22, 0xaa, 0xbb],
+            [0x11, 0x22, 0x33, 0xaa],
+        ];
+        let right = [
+            [0xdd, 0x22, 0x33, 0x44],
+            [0xcc, 0xdd, 0x33, 0x44],
+            [0xbb, 0xcc, 0xdd, 0x44],
+            [0xaa, 0xbb, 0xcc, 0xdd],
+        ];
+
+        for offset in 0..4 {
+            let mut memory = sample_memory(Endianness::Big);
+            memory.set_word_left(DATA + offset, 0xaabbccdd).unwrap();
+            assert_eq!(bytes(&memory), left[offset]);
+
+            let mut memory = sample_memory(Endianness::Big);
+            memory.set_word_right(DATA + offset, 0xaabbccdd).unwrap();
+            assert_eq!(bytes(&memory), right[offset]);
+        }
+    }
+
+    #[test]
+    fn swl_and_swr_little_endian() {
+        let left = [
+            [0xaa, 0x22, 0x33, 0x44],
+            [0xbb, 0xaa, 0x33, 0x44],
+            [0xcc, 0xbb, 0xaa, 0x44],
+            [0xdd, 0xcc, 0xbb, 0xaa],
+        ];
+        let right = [
+            [0xdd, 0xcc, 0xbb, 0xaa],
+            [0x11, 0xdd, 0xcc, 0xbb],
+            [0x11, 0x22, 0xdd, 0xcc],
+            [0x11, 0x22, 0x33, 0xdd],
+        ];
+
+        for offset in 0..4 {
+            let mut memory = sample_memory(Endianness::Little);
+            memory.set_word_left(DATA + offset, 0xaabbccdd).unwrap();
+            assert_eq!(bytes(&memory), left[offset]);
+
+            let mut memory = sample_memory(Endianness::Little);
+            memory.set_word_right(DATA + offset, 0xaabbccdd).unwrap();
+            assert_eq!(bytes(&memory), right[offset]);
+        }
+    }
+
+    #[test]
+    fn swl_and_swr_only_touch_the_bytes_they_store() {
+        let mut memory = sample_memory(Endianness::Big);
+        memory.watchpoints.add(Watchpoint {
+            on_read: true,
+            on_change: true,
+            ..Watchpoint::new(DATA, DATA)
+        });
+        memory.watchpoints.arm();
+
+        // swl at offset 1 stores bytes 1 to 3, and swr at offset 2 stores bytes 0 to 2.
+        memory.set_word_left(DATA + 1, 0xaabbccdd).unwrap();
+        assert!(memory.watchpoints.take_hits().is_empty());
+        assert_eq!(memory.get_last_writes(), &vec![(DATA + 1, 3)]);
+        assert!(!memory.was_last_written(DATA));
+
+        memory.clear_last_writes();
+        memory.set_word_right(DATA + 2, 0xaabbccdd).unwrap();
+        let hits = memory.watchpoints.take_hits();
+        assert_eq!(
+            hits.iter().map(|hit| hit.access).collect::<Vec<_>>(),
+            [WatchAccess::Write, WatchAccess::Change]
+        );
+        assert!(hits.iter().all(|hit| (hit.address, hit.size) == (DATA, 3)));
+        assert_eq!(memory.get_last_writes(), &vec![(DATA, 3)]);
+
+        memory.watchpoints.disarm();
+        assert_eq!(bytes(&memory), [0xbb, 0xcc, 0xdd, 0xcc]);
+    }
+
+    #[test]
+    fn lwl_and_lwr_load_an_unaligned_word() {
+        for endianness in [Endianness::Big, Endianness::Little] {
+            let mut memory = sample_memory(endianness);
+            memory.set_byte(DATA + 4, 0x55).unwrap();
+
+            // lwl from the most significant end and lwr from the least, as assemblers expand
+            // an unaligned `ulw`.
+            let (first, last) = match endianness {
+                Endianness::Big => (DATA + 1, DATA + 4),
+                Endianness::Little => (DATA + 4, DATA + 1),
+            };
+            let value = memory.get_word_left(first, 0).unwrap();
+            let value = memory.get_word_right(last, value).unwrap();
+
+            let expected = match endianness {
+                Endianness::Big => 0x22334455,
+                Endianness::Little => 0x55443322,
+            };
+            assert_eq!(value, expected);
+        }
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::mips::instruction::InstructionData;
+use crate::runtime::debug_info::DebugInfo;
+use crate::runtime::stepping::call_depth_change;
+
+/// The number of source lines listed as hot spots in the text report.
+const HOT_LINES: usize = 20;
+
+/// A function in a calling context. Each distinct chain of calls that reaches a function has
+/// its own node, so that a function called from two places has two nodes.
+struct ContextNode {
+    /// The address of the first instruction of the function.
+    function: usize,
+    parent: Option<usize>,
+    /// The nodes of the functions called from here, by their addresses.
+    children: HashMap<usize, usize>,
+    /// Instructions executed in this function in this context, not counting its callees.
+    count: u64,
+}
+
+/// Attributes executed instructions to addresses, and to functions by following the calls
+/// made with `jal` and `jalr` and the returns made with `jr $ra`.
+pub struct Profiler {
+    /// The number of times the instruction at each address was executed.
+    address_counts: HashMap<usize, u64>,
+    /// The calling context tree. The first node is the function the program started in.
+    nodes: Vec<ContextNode>,
+    /// The node of the function that is executing.
+    current: usize,
+}
+
+/// Instruction counts for one function, over all the contexts it was called in.
+pub struct FunctionProfile {
+    pub address: usize,
+    /// Instructions executed in the function and everything it called.
+    pub inclusive: u64,
+    /// Instructions executed in the function itself.
+    pub exclusive: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            address_counts: HashMap::new(),
+            nodes: vec![],
+            current: 0,
+        }
+    }
+
+    /// Count the instruction executed at `address`. `next` is the address execution
+    /// continues at, which is the start of the called function if the instruction is a call.
+    pub fn record(&mut self, address: usize, instruction: &InstructionData, next: usize) {
+        *self.address_counts.entry(address).or_insert(0) += 1;
+
+        if self.nodes.is_empty() {
+            self.add_node(address, None);
+        }
+
+        self.nodes[self.current].count += 1;
+
+        match call_depth_change(instruction) {
+            1 => {
+                self.current = match self.nodes[self.current].children.get(&next) {
+                    Some(child) => *child,
+                    None => self.add_node(next, Some(self.current)),
+                };
+            }
+            // A return from the outermost function has nowhere to go, so it is ignored.
+            -1 => {
+                if let Some(parent) = self.nodes[self.current].parent {
+                    self.current = parent;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn add_node(&mut self, function: usize, parent: Option<usize>) -> usize {
+        let index = self.nodes.len();
+
+        self.nodes.push(ContextNode {
+            function,
+            parent,
+            children: HashMap::new(),
+            count: 0,
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent].children.insert(function, index);
+        }
+
+        index
+    }
+
+    /// The functions on the call chain of a node, from the outermost to the node's own.
+    fn stack(&self, node: usize) -> Vec<usize> {
+        let mut stack = vec![];
+        let mut node = Some(node);
+
+        while let Some(index) = node {
+            stack.push(self.nodes[index].function);
+            node = self.nodes[index].parent;
+        }
+
+        stack.reverse();
+        stack
+    }
+
+    pub fn get_total(&self) -> u64 {
+        self.nodes.iter().map(|node| node.count).sum()
+    }
+
+    /// Inclusive and exclusive counts for each function, from the highest inclusive count.
+    /// Recursive calls are only counted once towards a function's inclusive count.
+    pub fn functions(&self) -> Vec<FunctionProfile> {
+        let mut functions: HashMap<usize, FunctionProfile> = HashMap::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let mut stack = self.stack(index);
+            stack.sort_unstable();
+            stack.dedup();
+
+            for function in stack {
+                let profile = functions.entry(function).or_insert(FunctionProfile {
+                    address: function,
+                    inclusive: 0,
+                    exclusive: 0,
+                });
+
+                profile.inclusive += node.count;
+
+                if function == node.function {
+                    profile.exclusive += node.count;
+                }
+            }
+        }
+
+        let mut functions: Vec<_> = functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.inclusive
+                .cmp(&a.inclusive)
+                .then(b.exclusive.cmp(&a.exclusive))
+                .then(a.address.cmp(&b.address))
+        });
+
+        functions
+    }
+
+    /// The number of instructions executed under each label, counting each instruction
+    /// towards the closest label at or before it, from the most executed. Instructions
+    /// before the first label are counted under "(no label)".
+    pub fn labels<'a>(&self, debug_info: &'a DebugInfo) -> Vec<(&'a str, u64)> {
+        let mut labels: HashMap<&str, u64> = HashMap::new();
+
+        for (address, count) in &self.address_counts {
+            let label = debug_info
+                .enclosing_symbol(*address)
+                .map_or("(no label)", |(label, _)| label);
+
+            *labels.entry(label).or_insert(0) += count;
+        }
+
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        labels
+    }
+
+    /// The number of instructions executed from each source line, from the most executed.
+    pub fn lines(&self, debug_info: &DebugInfo) -> Vec<(usize, u64)> {
+        let mut lines: HashMap<usize, u64> = HashMap::new();
+
+        for (address, count) in &self.address_counts {
+            if let Some(line) = debug_info.line_of(*address) {
+                *lines.entry(line).or_insert(0) += count;
+            }
+        }
+
+        let mut lines: Vec<_> = lines.into_iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        lines
+    }
+
+    /// A plain text report of the functions, labels and source lines that executed the most
+    /// instructions.
+    pub fn report(&self, debug_info: &DebugInfo) -> String {
+        let total = self.get_total();
+        let percentage = |count: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                count as f64 * 100.0 / total as f64
+            }
+        };
+
+        let mut report = String::new();
+
+        writeln!(report, "Instructions profiled: {}", total).unwrap();
+
+        let lines = self.lines(debug_info);
+
+        if debug_info.get_symbols().is_empty() {
+            writeln!(
+                report,
+                "No labels were given with --symbols, so functions are shown by address"
+            )
+            .unwrap();
+        }
+
+        if lines.is_empty() {
+            writeln!(
+                report,
+                "No source lines were given with --symbols, so hot lines are left out"
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nFunctions:").unwrap();
+        writeln!(
+            report,
+            "    {:>12} {:>7} {:>12} {:>7}  Function",
+            "Inclusive", "", "Exclusive", ""
+        )
+        .unwrap();
+        for function in self.functions() {
+            writeln!(
+                report,
+                "    {:>12} {:>6.1}% {:>12} {:>6.1}%  {}",
+                function.inclusive,
+                percentage(function.inclusive),
+                function.exclusive,
+                percentage(function.exclusive),
+                location_name(debug_info, function.address)
+            )
+            .unwrap();
+        }
+
+        // Without labels, every instruction would be under "(no label)".
+        if !debug_info.get_symbols().is_empty() {
+            writeln!(report, "\nLabels:").unwrap();
+            for (label, count) in self.labels(debug_info) {
+                writeln!(
+                    report,
+                    "    {:>12} {:>6.1}%  {}",
+                    count,
+                    percentage(count),
+                    label
+                )
+                .unwrap();
+            }
+        }
+
+        if !lines.is_empty() {
+            writeln!(report, "\nHot lines:").unwrap();
+            for (line, count) in lines.into_iter().take(HOT_LINES) {
+                writeln!(
+                    report,
+                    "    {:>12} {:>6.1}%  {:>5}: {}",
+                    count,
+                    percentage(count),
+                    line,
+                    debug_info.source_line(line).unwrap_or("").trim()
+                )
+                .unwrap();
+            }
+        }
+
+        report
+    }
+
+    /// The calling contexts in the folded stack format read by flamegraph tools: one line
+    /// per context, with the function names separated by semicolons, then a space and the
+    /// number of instructions executed in that context.
+    pub fn folded(&self, debug_info: &DebugInfo) -> String {
+        let mut folded = String::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.count == 0 {
+                continue;
+            }
+
+            let names: Vec<String> = self
+                .stack(index)
+                .into_iter()
+                .map(|function| location_name(debug_info, function))
+                .collect();
+
+            writeln!(folded, "{} {}", names.join(";"), node.count).unwrap();
+        }
+
+        folded
+    }
+}
+
+/// Name an address by the closest label at or before it, or by the address itself if there
+/// is no such label.
+fn location_name(debug_info: &DebugInfo, address: usize) -> String {
+    match debug_info.enclosing_symbol(address) {
+        Some((label, _)) => label.to_owned(),
+        None => format!("{:#010x}", address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runtime::debug_info::DebugInfo;
+    use crate::runtime::loader::{initialize, load_words, vm_running};
+    use crate::runtime::profiler::Profiler;
+    use crate::runtime::stepping::{Run, RunTarget, StopReason};
+    use crate::runtime::vm::{MemoryLayout, VM};
+
+    /// The report of `li $t0, 1; li $t1, 2`, profiled with the given debug information.
+    fn report(debug_info: DebugInfo) -> String {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+        vm.debug_info = debug_info;
+        vm.profiler = Some(Profiler::new());
+        load_words(&mut vm, &[0x24080001, 0x24090002]);
+        vm.run(&mut Run::new(RunTarget::Continue), 2).unwrap();
+
+        vm.profiler.as_ref().unwrap().report(&vm.debug_info)
+    }
+
+    #[test]
+    fn report_without_debug_info_says_so() {
+        let report = report(DebugInfo::new());
+
+        assert!(report.contains("Instructions profiled: 2"));
+        assert!(report.contains("functions are shown by address"));
+        assert!(report.contains("hot lines are left out"));
+        assert!(report.contains("0x00400000"));
+        assert!(!report.contains("Labels:"));
+        assert!(!report.contains("Hot lines:"));
+    }
+
+    #[test]
+    fn report_with_debug_info_names_functions_and_lines() {
+        let mut debug_info =
+            DebugInfo::parse("symbol main 0x00400000\nline 1 0x00400000\nline 2 0x00400004")
+                .unwrap();
+        debug_info.set_source("li $t0, 1\nli $t1, 2");
+        let report = report(debug_info);
+
+        assert!(!report.contains("--symbols"));
+        assert!(report.contains("main"));
+        assert!(report.contains("Labels:"));
+        assert!(report.contains("2: li $t1, 2"));
+    }
+
+    /// The labels of the program profiled by `profile_calls`.
+    const CALL_SYMBOLS: &str = "symbol main 0x00400000\nsymbol f 0x00400010\nsymbol g 0x00400030";
+
+    /// Profile a program whose `main` calls `f`, which calls itself twice more, and then
+    /// `g`. `f` saves `$ra` on the stack around its recursive call.
+    fn profile_calls() -> Profiler {
+        let mut vm = vm_running(&[
+            0x24040002, // main: addiu $a0, $zero, 2
+            0x0c100004, //       jal f
+            0x0c10000c, //       jal g
+            0x00000000, //       (halt)
+            0x10800006, // f:    beq $a0, $zero, 6
+            0x27bdfffc, //       addiu $sp, $sp, -4
+            0xafbf0000, //       sw $ra, 0($sp)
+            0x2484ffff, //       addiu $a0, $a0, -1
+            0x0c100004, //       jal f
+            0x8fbf0000, //       lw $ra, 0($sp)
+            0x27bd0004, //       addiu $sp, $sp, 4
+            0x03e00008, //       jr $ra
+            0x03e00008, // g:    jr $ra
+        ]);
+        initialize(&mut vm, &[]).unwrap();
+        vm.profiler = Some(Profiler::new());
+
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Continue), 100),
+            Ok(Some(StopReason::Halted))
+        ));
+
+        vm.profiler.unwrap()
+    }
+
+    #[test]
+    fn calls_are_counted_inclusively_and_exclusively() {
+        let profiler = profile_calls();
+        assert_eq!(profiler.get_total(), 22);
+
+        let functions: Vec<_> = profiler
+            .functions()
+            .iter()
+            .map(|function| (function.address, function.inclusive, function.exclusive))
+            .collect();
+
+        // The recursive calls to f only count once towards its inclusive count.
+        assert_eq!(
+            functions,
+            [
+                (0x00400000, 22, 3),
+                (0x00400010, 18, 18),
+                (0x00400030, 1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn folded_stacks_name_each_calling_context() {
+        let profiler = profile_calls();
+        let debug_info = DebugInfo::parse(CALL_SYMBOLS).unwrap();
+
+        assert_eq!(
+            profiler.folded(&debug_info),
+            "main 3\nmain;f 8\nmain;f;f 8\nmain;f;f;f 2\nmain;g 1\n"
+        );
+        assert_eq!(
+            profiler.folded(&DebugInfo::new()).lines().next(),
+            Some("0x00400000 3")
+        );
+    }
+}
@@ -1,66 +1,276 @@
+use std::fmt;
+
+use crate::runtime::errors::RuntimeError;
 use crate::runtime::vm::VM;
 
-const REG_V0: u8 = 2;
-const REG_V1: u8 = 3;
-const REG_A0: u8 = 4;
-const REG_A1: u8 = 5;
-const REG_A2: u8 = 6;
-const REG_A3: u8 = 7;
-const REG_T0: u8 = 8;
-const REG_T1: u8 = 9;
-const REG_T2: u8 = 10;
-const REG_T3: u8 = 11;
-const REG_T4: u8 = 12;
-const REG_T5: u8 = 13;
-const REG_T6: u8 = 14;
-const REG_T7: u8 = 15;
-const REG_S0: u8 = 16;
-const REG_S1: u8 = 17;
-const REG_S2: u8 = 18;
-const REG_S3: u8 = 19;
-const REG_S4: u8 = 20;
-const REG_S5: u8 = 21;
-const REG_S6: u8 = 22;
-const REG_S7: u8 = 23;
-const REG_T8: u8 = 24;
-const REG_T9: u8 = 25;
-const REG_K0: u8 = 26;
-const REG_K1: u8 = 27;
-const REG_GP: u8 = 28;
-const REG_SP: u8 = 29;
-const REG_FP: u8 = 30;
-const REG_RA: u8 = 31;
+pub const REG_V0: u8 = 2;
+pub const REG_V1: u8 = 3;
+pub const REG_A0: u8 = 4;
+pub const REG_A1: u8 = 5;
+pub const REG_A2: u8 = 6;
+pub const REG_A3: u8 = 7;
+pub const REG_T0: u8 = 8;
+pub const REG_T1: u8 = 9;
+pub const REG_T2: u8 = 10;
+pub const REG_T3: u8 = 11;
+pub const REG_T4: u8 = 12;
+pub const REG_T5: u8 = 13;
+pub const REG_T6: u8 = 14;
+pub const REG_T7: u8 = 15;
+pub const REG_S0: u8 = 16;
+pub const REG_S1: u8 = 17;
+pub const REG_S2: u8 = 18;
+pub const REG_S3: u8 = 19;
+pub const REG_S4: u8 = 20;
+pub const REG_S5: u8 = 21;
+pub const REG_S6: u8 = 22;
+pub const REG_S7: u8 = 23;
+pub const REG_T8: u8 = 24;
+pub const REG_T9: u8 = 25;
+pub const REG_K0: u8 = 26;
+pub const REG_K1: u8 = 27;
+pub const REG_GP: u8 = 28;
+pub const REG_SP: u8 = 29;
+pub const REG_FP: u8 = 30;
+pub const REG_RA: u8 = 31;
+
+/// The conventional names of the general purpose registers, without the `$`.
+pub const REGISTER_NAMES: [&str; 32] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+    "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp", "fp",
+    "ra",
+];
+
+/// Get the conventional name of a general purpose register, without the `$`, or None if
+/// there is no such register.
+pub fn register_name(number: u8) -> Option<&'static str> {
+    REGISTER_NAMES.get(number as usize).copied()
+}
+
+/// Parse a register number below 32, given as plain decimal digits without leading zeros.
+fn parse_register_number(text: &str) -> Option<u8> {
+    if text.is_empty()
+        || !text.bytes().all(|byte| byte.is_ascii_digit())
+        || (text.len() > 1 && text.starts_with('0'))
+    {
+        return None;
+    }
+
+    text.parse().ok().filter(|&number| number < 32)
+}
+
+/// Any register that can be named in assembly, expressions or the debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    General(u8),
+    /// A floating point register of coprocessor 1.
+    Float(u8),
+    Hi,
+    Lo,
+    Pc,
+}
+
+impl Register {
+    /// Parse a register name, with or without the `$`. General purpose registers can be
+    /// given by name or number, such as `$t0` or `$8`, and floating point registers as `$f12`.
+    pub fn parse(name: &str) -> Option<Register> {
+        let name = name.strip_prefix('$').unwrap_or(name);
+
+        match name {
+            "hi" => return Some(Register::Hi),
+            "lo" => return Some(Register::Lo),
+            "pc" => return Some(Register::Pc),
+            // $s8 is another name for the frame pointer.
+            "s8" => return Some(Register::General(REG_FP)),
+            _ => {}
+        }
+
+        let numbered = |text: &str| text.starts_with(|c: char| c.is_ascii_digit());
+
+        if numbered(name) {
+            return parse_register_number(name).map(Register::General);
+        }
+
+        // Not $fp, which is a general purpose register.
+        if let Some(number) = name.strip_prefix('f').filter(|number| numbered(number)) {
+            return parse_register_number(number).map(Register::Float);
+        }
+
+        REGISTER_NAMES
+            .iter()
+            .position(|register| *register == name)
+            .map(|number| Register::General(number as u8))
+    }
+}
+
+/// Formats general purpose and floating point registers as in assembly, such as `$t0` and
+/// `$f12`, and the others by their bare names.
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::General(number) => match register_name(*number) {
+                Some(name) => write!(f, "${}", name),
+                None => write!(f, "${}", number),
+            },
+            Register::Float(number) => write!(f, "$f{}", number),
+            Register::Hi => write!(f, "hi"),
+            Register::Lo => write!(f, "lo"),
+            Register::Pc => write!(f, "pc"),
+        }
+    }
+}
+
+impl VM {
+    /// Get the value of any register. Floating point registers give their raw bits.
+    pub fn get_any_register(&self, register: Register) -> Result<u32, RuntimeError> {
+        match register {
+            Register::General(number) => self.get_register(number),
+            Register::Float(number) => self.fpu.get_register(number),
+            Register::Hi => Ok(self.get_hi()),
+            Register::Lo => Ok(self.get_lo()),
+            Register::Pc => Ok(self.get_pc() as u32),
+        }
+    }
+
+    /// Set the value of any register. Floating point registers are set to the raw bits.
+    pub fn set_any_register(&mut self, register: Register, value: u32) -> Result<(), RuntimeError> {
+        match register {
+            Register::General(number) => self.set_register(number, value)?,
+            Register::Float(number) => self.fpu.set_register(number, value)?,
+            Register::Hi => self.set_hi(value),
+            Register::Lo => self.set_lo(value),
+            Register::Pc => self.set_pc(value as usize),
+        }
+
+        Ok(())
+    }
+}
 
 impl VM {
+    pub fn get_v0(&self) -> u32 {
+        self.get_register(REG_V0).unwrap()
+    }
+
+    pub fn set_v0(&mut self, value: u32) {
+        self.set_register(REG_V0, value).unwrap();
+    }
+
+    pub fn get_a0(&self) -> u32 {
+        self.get_register(REG_A0).unwrap()
+    }
+
+    pub fn set_a0(&mut self, value: u32) {
+        self.set_register(REG_A0, value).unwrap();
+    }
+
+    pub fn get_a1(&self) -> u32 {
+        self.get_register(REG_A1).unwrap()
+    }
+
+    pub fn set_a1(&mut self, value: u32) {
+        self.set_register(REG_A1, value).unwrap();
+    }
+
     pub fn get_gp(&self) -> u32 {
-        self.get_register(28).unwrap()
+        self.get_register(REG_GP).unwrap()
     }
 
     pub fn set_gp(&mut self, value: u32) {
-        self.set_register(28, value).unwrap();
+        self.set_register(REG_GP, value).unwrap();
     }
 
     pub fn get_sp(&self) -> u32 {
-        self.get_register(29).unwrap()
+        self.get_register(REG_SP).unwrap()
     }
 
     pub fn set_sp(&mut self, value: u32) {
-        self.set_register(29, value).unwrap();
+        self.set_register(REG_SP, value).unwrap();
     }
 
     pub fn get_fp(&self) -> u32 {
-        self.get_register(30).unwrap()
+        self.get_register(REG_FP).unwrap()
     }
 
     pub fn set_fp(&mut self, value: u32) {
-        self.set_register(30, value).unwrap();
+        self.set_register(REG_FP, value).unwrap();
     }
 
     pub fn get_ra(&self) -> u32 {
-        self.get_register(31).unwrap()
+        self.get_register(REG_RA).unwrap()
     }
 
     pub fn set_ra(&mut self, value: u32) {
-        self.set_register(31, value).unwrap();
+        self.set_register(REG_RA, value).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::vm::MemoryLayout;
+
+    #[test]
+    fn names_and_numbers_round_trip() {
+        for number in 0..32 {
+            let general = Register::General(number);
+            let float = Register::Float(number);
+
+            assert_eq!(Register::parse(&format!("${}", number)), Some(general));
+            assert_eq!(
+                Register::parse(REGISTER_NAMES[number as usize]),
+                Some(general)
+            );
+            assert_eq!(Register::parse(&general.to_string()), Some(general));
+            assert_eq!(Register::parse(&float.to_string()), Some(float));
+        }
+
+        assert_eq!(Register::parse("$t0"), Some(Register::General(REG_T0)));
+        assert_eq!(Register::parse("$s8"), Some(Register::General(REG_FP)));
+        assert_eq!(Register::parse("$f12"), Some(Register::Float(12)));
+        assert_eq!(Register::General(REG_RA).to_string(), "$ra");
+        assert_eq!(register_name(32), None);
+    }
+
+    #[test]
+    fn hi_lo_and_pc_are_named() {
+        for (name, register) in [
+            ("hi", Register::Hi),
+            ("lo", Register::Lo),
+            ("pc", Register::Pc),
+        ] {
+            assert_eq!(Register::parse(name), Some(register));
+            assert_eq!(Register::parse(&format!("${}", name)), Some(register));
+            assert_eq!(register.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn only_plain_numbers_are_accepted() {
+        for name in [
+            "", "$", "$+8", "$08", "$-1", "$32", "$f", "$f+1", "$f01", "$f32", "$t10", "$F1",
+        ] {
+            assert_eq!(Register::parse(name), None, "{:?} parsed", name);
+        }
+    }
+
+    #[test]
+    fn registers_are_got_and_set_by_name() {
+        let mut vm = VM::new(MemoryLayout::mars(0x1000, 0x1000));
+
+        for (name, value) in [
+            ("$t0", 5),
+            ("$f12", 0x3f800000),
+            ("hi", 7),
+            ("lo", 8),
+            ("pc", 0x00400010),
+        ] {
+            let register = Register::parse(name).unwrap();
+            vm.set_any_register(register, value).unwrap();
+            assert_eq!(vm.get_any_register(register).unwrap(), value, "{}", name);
+        }
+
+        assert_eq!(vm.get_register(REG_T0).unwrap(), 5);
+        assert_eq!(vm.fpu.get_float(12).unwrap(), 1.0);
+        assert_eq!(vm.get_pc(), 0x00400010);
     }
 }
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::mips::instruction::{InstructionData, InstructionFormat};
+
+/// A broad class of instruction, as in the MARS "Instruction Statistics" tool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionCategory {
+    Alu,
+    Memory,
+    Branch,
+    Jump,
+    /// Syscalls and anything else that does not fit the other categories.
+    Other,
+}
+
+impl InstructionCategory {
+    pub const ALL: [InstructionCategory; 5] = [
+        InstructionCategory::Alu,
+        InstructionCategory::Memory,
+        InstructionCategory::Branch,
+        InstructionCategory::Jump,
+        InstructionCategory::Other,
+    ];
+
+    pub fn of(name: &str) -> InstructionCategory {
+        match name {
+            "j" | "jal" | "jr" | "jalr" => InstructionCategory::Jump,
+            "syscall" => InstructionCategory::Other,
+            _ if is_load(name) || is_store(name) => InstructionCategory::Memory,
+            _ if name.starts_with('b') => InstructionCategory::Branch,
+            _ => InstructionCategory::Alu,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InstructionCategory::Alu => "ALU",
+            InstructionCategory::Memory => "Memory",
+            InstructionCategory::Branch => "Branch",
+            InstructionCategory::Jump => "Jump",
+            InstructionCategory::Other => "Other",
+        }
+    }
+
+    fn index(&self) -> usize {
+        InstructionCategory::ALL
+            .iter()
+            .position(|category| category == self)
+            .unwrap()
+    }
+}
+
+fn is_load(name: &str) -> bool {
+    matches!(
+        name,
+        "lb" | "lbu" | "lh" | "lhu" | "lw" | "lwl" | "lwr" | "ll" | "lwc1" | "ldc1"
+    )
+}
+
+fn is_store(name: &str) -> bool {
+    matches!(
+        name,
+        "sb" | "sh" | "sw" | "swl" | "swr" | "sc" | "swc1" | "sdc1"
+    )
+}
+
+/// The number of clock cycles an instruction takes on the multicycle datapath from Patterson
+/// and Hennessy: five for loads, four for stores and ALU instructions, and three for
+/// branches and jumps.
+fn cycles(name: &str) -> u64 {
+    match InstructionCategory::of(name) {
+        InstructionCategory::Memory if is_load(name) => 5,
+        InstructionCategory::Branch | InstructionCategory::Jump => 3,
+        _ => 4,
+    }
+}
+
+fn format_index(format: InstructionFormat) -> usize {
+    match format {
+        InstructionFormat::R => 0,
+        InstructionFormat::I => 1,
+        InstructionFormat::J => 2,
+    }
+}
+
+/// Counts of the instructions executed by the program.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    total: u64,
+    cycles: u64,
+    by_mnemonic: HashMap<&'static str, u64>,
+    /// Counts of R, I and J-format instructions, in that order.
+    by_format: [u64; 3],
+    /// Counts in the order of `InstructionCategory::ALL`.
+    by_category: [u64; 5],
+}
+
+impl Statistics {
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    /// Count an executed instruction.
+    pub fn record(&mut self, instruction: &InstructionData<'static>) {
+        let name = instruction.base.name;
+
+        self.total += 1;
+        self.cycles += cycles(name);
+        *self.by_mnemonic.entry(name).or_insert(0) += 1;
+        self.by_format[format_index(instruction.base.format)] += 1;
+        self.by_category[InstructionCategory::of(name).index()] += 1;
+    }
+
+    /// Take back an instruction counted by `record`, such as when it is undone.
+    pub fn forget(&mut self, instruction: &InstructionData<'static>) {
+        let name = instruction.base.name;
+
+        self.total -= 1;
+        self.cycles -= cycles(name);
+
+        if let Some(count) = self.by_mnemonic.get_mut(name) {
+            *count -= 1;
+
+            if *count == 0 {
+                self.by_mnemonic.remove(name);
+            }
+        }
+
+        self.by_format[format_index(instruction.base.format)] -= 1;
+        self.by_category[InstructionCategory::of(name).index()] -= 1;
+    }
+
+    pub fn get_total(&self) -> u64 {
+        self.total
+    }
+
+    /// The estimated number of clock cycles taken by the instructions on a multicycle
+    /// datapath.
+    pub fn get_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// The average number of cycles per instruction, or 0 if none have executed.
+    pub fn get_cpi(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.cycles as f64 / self.total as f64
+        }
+    }
+
+    pub fn get_format_count(&self, format: InstructionFormat) -> u64 {
+        self.by_format[format_index(format)]
+    }
+
+    pub fn get_category_count(&self, category: InstructionCategory) -> u64 {
+        self.by_category[category.index()]
+    }
+
+    /// The executed mnemonics and their counts, from the most to the least executed.
+    pub fn get_mnemonic_counts(&self) -> Vec<(&'static str, u64)> {
+        let mut counts: Vec<_> = self
+            .by_mnemonic
+            .iter()
+            .map(|(name, count)| (*name, *count))
+            .collect();
+
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    /// The percentage of all executed instructions that `count` makes up.
+    pub fn percentage(&self, count: u64) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// A plain text report, as printed by `--stats`.
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Instructions executed: {}", self.total)?;
+        writeln!(
+            f,
+            "Estimated cycles:      {} (CPI {:.2})",
+            self.cycles,
+            self.get_cpi()
+        )?;
+
+        writeln!(f, "\nBy format:")?;
+        for (name, format) in [
+            ("R", InstructionFormat::R),
+            ("I", InstructionFormat::I),
+            ("J", InstructionFormat::J),
+        ] {
+            let count = self.get_format_count(format);
+            writeln!(
+                f,
+                "    {:<10} {:>12} {:>6.1}%",
+                name,
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        writeln!(f, "\nBy category:")?;
+        for category in InstructionCategory::ALL {
+            let count = self.get_category_count(category);
+            writeln!(
+                f,
+                "    {:<10} {:>12} {:>6.1}%",
+                category.name(),
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        writeln!(f, "\nBy instruction:")?;
+        for (name, count) in self.get_mnemonic_counts() {
+            writeln!(
+                f,
+                "    {:<10} {:>12} {:>6.1}%",
+                name,
+                count,
+                self.percentage(count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::loader::vm_running;
+
+    /// `lw $t0, 0($t1); sw $t0, 0($t1); addu $t0, $t1, $t2; beq $t0, $t1, 1; j 0x00400000`
+    const PROGRAM: [u32; 5] = [0x8d280000, 0xad280000, 0x012a4021, 0x11090001, 0x08100000];
+
+    fn decoded(words: &[u32]) -> Vec<InstructionData<'static>> {
+        let vm = vm_running(&[]);
+        words
+            .iter()
+            .map(|word| vm.decode_instruction(*word).unwrap())
+            .collect()
+    }
+
+    fn recorded(instructions: &[InstructionData<'static>]) -> Statistics {
+        let mut statistics = Statistics::new();
+
+        for instruction in instructions {
+            statistics.record(instruction);
+        }
+
+        statistics
+    }
+
+    /// Everything a report shows, for comparing two sets of statistics.
+    fn summary(statistics: &Statistics) -> (u64, u64, Vec<(&'static str, u64)>, Vec<u64>) {
+        let formats = [
+            InstructionFormat::R,
+            InstructionFormat::I,
+            InstructionFormat::J,
+        ]
+        .map(|format| statistics.get_format_count(format));
+        let categories =
+            InstructionCategory::ALL.map(|category| statistics.get_category_count(category));
+
+        (
+            statistics.get_total(),
+            statistics.get_cycles(),
+            statistics.get_mnemonic_counts(),
+            formats.into_iter().chain(categories).collect(),
+        )
+    }
+
+    #[test]
+    fn instructions_are_categorised_by_name() {
+        for (name, category) in [
+            ("bc1t", InstructionCategory::Branch),
+            ("beq", InstructionCategory::Branch),
+            ("lwc1", InstructionCategory::Memory),
+            ("sdc1", InstructionCategory::Memory),
+            ("jalr", InstructionCategory::Jump),
+            ("j", InstructionCategory::Jump),
+            ("syscall", InstructionCategory::Other),
+            ("addu", InstructionCategory::Alu),
+            ("add.s", InstructionCategory::Alu),
+        ] {
+            assert_eq!(InstructionCategory::of(name), category, "{}", name);
+        }
+    }
+
+    #[test]
+    fn cycles_are_weighted_by_category() {
+        let statistics = recorded(&decoded(&PROGRAM));
+
+        // 5 for the load, 4 for the store and addu, and 3 for the branch and jump.
+        assert_eq!(statistics.get_total(), 5);
+        assert_eq!(statistics.get_cycles(), 19);
+        assert_eq!(statistics.get_cpi(), 3.8);
+        assert_eq!(Statistics::new().get_cpi(), 0.0);
+
+        assert_eq!(statistics.get_format_count(InstructionFormat::I), 3);
+        assert_eq!(
+            statistics.get_category_count(InstructionCategory::Memory),
+            2
+        );
+        assert_eq!(statistics.percentage(2), 40.0);
+    }
+
+    #[test]
+    fn forget_takes_back_what_record_counted() {
+        let instructions = decoded(&PROGRAM);
+        let mut statistics = recorded(&instructions);
+
+        statistics.forget(&instructions[4]);
+        statistics.forget(&instructions[0]);
+        assert_eq!(
+            summary(&statistics),
+            summary(&recorded(&instructions[1..4]))
+        );
+
+        for instruction in &instructions[1..4] {
+            statistics.forget(instruction);
+        }
+        assert_eq!(summary(&statistics), summary(&Statistics::new()));
+    }
+}
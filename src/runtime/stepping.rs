@@ -0,0 +1,274 @@
+use crate::mips::instruction::{coerece_r_format, InstructionData};
+use crate::runtime::errors::{RuntimeError, Trap};
+use crate::runtime::vm::VM;
+use crate::runtime::watchpoints::WatchpointHit;
+
+/// How far a run should go before stopping on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTarget {
+    /// Run until a breakpoint or the end of the program.
+    Continue,
+    /// Execute a single instruction.
+    Step,
+    /// Execute a single instruction, running any function it calls until it returns.
+    StepOver,
+    /// Run until the current function returns to its caller with `jr $ra`.
+    StepOut,
+}
+
+/// Why a run stopped.
+pub enum StopReason {
+    /// The run target was reached.
+    Reached,
+    /// The next instruction has a breakpoint.
+    Breakpoint(usize),
+    /// The instruction at `address` triggered a watchpoint.
+    Watchpoint {
+        hit: WatchpointHit,
+        address: usize,
+    },
+    /// A null instruction was reached.
+    Halted,
+    /// The program exited with the given code.
+    Exited(i32),
+    Trap(Trap),
+    /// A read syscall is waiting for input.
+    WaitingForInput,
+    /// Stepping backwards reached the oldest instruction in the journal.
+    StartOfHistory,
+}
+
+/// A run of the VM towards a target, which can be continued across several calls to
+/// `VM::run`.
+pub struct Run {
+    target: RunTarget,
+    /// Calls made minus returns taken since the run started.
+    depth: i32,
+    /// Whether any instruction has been executed yet. A run that starts where the VM last
+    /// stopped does not check breakpoints before its first instruction, so that it can
+    /// continue from the breakpoint it stopped at.
+    started: bool,
+}
+
+impl Run {
+    pub fn new(target: RunTarget) -> Run {
+        Run {
+            target,
+            depth: 0,
+            started: false,
+        }
+    }
+
+    pub fn get_target(&self) -> RunTarget {
+        self.target
+    }
+
+    /// Whether the target has been reached after an instruction was executed.
+    fn reached(&self, vm: &VM) -> bool {
+        // A return is not complete until the instruction in its delay slot has executed.
+        let settled = vm.get_delayed_branch().is_none();
+
+        match self.target {
+            RunTarget::Continue => false,
+            RunTarget::Step => true,
+            RunTarget::StepOver => self.depth <= 0 && settled,
+            RunTarget::StepOut => self.depth < 0 && settled,
+        }
+    }
+}
+
+impl VM {
+    /// Continue a run, executing at most `max_steps` instructions. Return why the run
+    /// stopped, or None if it used up its steps first.
+    pub fn run(
+        &mut self,
+        run: &mut Run,
+        max_steps: usize,
+    ) -> Result<Option<StopReason>, RuntimeError> {
+        let result = self.run_steps(run, max_steps);
+
+        if let Ok(Some(_)) = result {
+            self.stopped_at = Some(self.get_pc());
+        }
+
+        result
+    }
+
+    fn run_steps(
+        &mut self,
+        run: &mut Run,
+        max_steps: usize,
+    ) -> Result<Option<StopReason>, RuntimeError> {
+        for _ in 0..max_steps {
+            let resuming = !run.started && self.stopped_at == Some(self.get_pc());
+
+            if !resuming && self.at_breakpoint() {
+                return Ok(Some(StopReason::Breakpoint(self.get_pc())));
+            }
+
+            run.started = true;
+            self.stopped_at = None;
+
+            let address = self.get_pc();
+            // Traces record FPU changes by comparing against the state before.
+            let fpu = self.tracer.is_some().then(|| self.fpu.clone());
+            let result = self.run_single_instruction();
+            // Take the hits even if the instruction failed, so that they are not reported
+            // against the next one.
+            let hits = self.memory.watchpoints.take_hits();
+            let (instruction, trap) = result?;
+
+            if instruction.is_null() {
+                return Ok(Some(StopReason::Halted));
+            }
+
+            // A read syscall waiting for input runs again once it has some, so it is only
+            // counted then.
+            if self.get_input_request().is_none() {
+                self.count_instruction(instruction);
+
+                let next = self.get_delayed_branch().unwrap_or(self.get_pc());
+
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.record(address, &instruction, next);
+                }
+
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record(address, &instruction, next);
+                }
+
+                if let Some(fpu) = &fpu {
+                    self.trace_step(address, &instruction, fpu);
+                }
+            }
+
+            // Track calls and returns, so that stepping over or out of a function also
+            // steps over any recursive calls it makes.
+            run.depth += call_depth_change(&instruction);
+
+            if let Some(trap) = trap {
+                return Ok(Some(StopReason::Trap(trap)));
+            }
+
+            if let Some(hit) = hits.first() {
+                return Ok(Some(StopReason::Watchpoint { hit: *hit, address }));
+            }
+
+            if let Some(code) = self.get_exit_code() {
+                return Ok(Some(StopReason::Exited(code)));
+            }
+
+            if self.get_input_request().is_some() {
+                // The syscall runs again when the run continues, and its breakpoints have
+                // already been checked.
+                run.started = false;
+                return Ok(Some(StopReason::WaitingForInput));
+            }
+
+            if run.reached(self) {
+                return Ok(Some(StopReason::Reached));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Return 1 for an instruction that calls a function, -1 for one that returns from one with
+/// `jr $ra`, and 0 otherwise.
+pub(super) fn call_depth_change(instruction: &InstructionData) -> i32 {
+    match instruction.base.name {
+        "jal" | "jalr" => 1,
+        "jr" if coerece_r_format(instruction).rs == 31 => -1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::breakpoints::{Breakpoint, BreakpointLocation};
+    use crate::runtime::loader::{vm_running, DATA};
+    use crate::runtime::watchpoints::Watchpoint;
+
+    #[test]
+    fn breakpoint_on_a_read_syscall_counts_one_hit() {
+        // addiu $v0, $zero, 5; syscall
+        let mut vm = vm_running(&[0x24020005, 0x0000000c]);
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400004)));
+        let mut run = Run::new(RunTarget::Continue);
+
+        assert!(matches!(
+            vm.run(&mut run, 10),
+            Ok(Some(StopReason::Breakpoint(0x00400004)))
+        ));
+
+        let mut run = Run::new(RunTarget::Continue);
+        assert!(matches!(
+            vm.run(&mut run, 10),
+            Ok(Some(StopReason::WaitingForInput))
+        ));
+
+        vm.push_input("3\n");
+        assert!(matches!(vm.run(&mut run, 10), Ok(Some(StopReason::Halted))));
+        assert_eq!(vm.get_v0(), 3);
+        assert_eq!(vm.get_breakpoints()[0].hits, 1);
+    }
+
+    #[test]
+    fn breakpoint_on_the_first_instruction_stops_once() {
+        // addiu $t0, $zero, 1; addiu $t0, $t0, 1
+        let mut vm = vm_running(&[0x24080001, 0x25080001]);
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400000)));
+
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Continue), 10),
+            Ok(Some(StopReason::Breakpoint(0x00400000)))
+        ));
+        assert_eq!(vm.get_register(8).unwrap(), 0);
+
+        // Continuing from the breakpoint runs the instruction it stopped before.
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Continue), 10),
+            Ok(Some(StopReason::Halted))
+        ));
+        assert_eq!(vm.get_register(8).unwrap(), 2);
+        assert_eq!(vm.get_breakpoints()[0].hits, 1);
+    }
+
+    #[test]
+    fn breakpoint_stepped_onto_is_passed_by_the_next_run() {
+        // addiu $t0, $zero, 1; addiu $t0, $t0, 1
+        let mut vm = vm_running(&[0x24080001, 0x25080001]);
+        vm.add_breakpoint(Breakpoint::new(BreakpointLocation::Address(0x00400004)));
+
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Step), 10),
+            Ok(Some(StopReason::Reached))
+        ));
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Continue), 10),
+            Ok(Some(StopReason::Halted))
+        ));
+        assert_eq!(vm.get_register(8).unwrap(), 2);
+    }
+
+    #[test]
+    fn watchpoint_hits_of_a_failed_instruction_are_dropped() {
+        // ldc1 $f1, 0($t1), which reads memory and then fails on the odd register;
+        // addiu $t0, $zero, 1
+        let mut vm = vm_running(&[0xd5210000, 0x24080001]);
+        vm.memory.watchpoints.add(Watchpoint {
+            on_read: true,
+            ..Watchpoint::new(DATA, DATA + 7)
+        });
+
+        assert!(vm.run(&mut Run::new(RunTarget::Step), 1).is_err());
+
+        // The failed instruction has moved the PC on, so the next one runs.
+        assert!(matches!(
+            vm.run(&mut Run::new(RunTarget::Step), 1),
+            Ok(Some(StopReason::Reached))
+        ));
+    }
+}
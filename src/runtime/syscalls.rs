@@ -0,0 +1,298 @@
+use std::fmt::{Display, LowerExp};
+
+use crate::runtime::errors::{FatalErrorType, RuntimeError, Trap};
+use crate::runtime::vm::VM;
+
+/// The kind of input a read syscall is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputRequest {
+    Integer,
+    Float,
+    Double,
+    /// A line of at most `max_length` - 1 characters.
+    String {
+        max_length: u32,
+    },
+    Character,
+}
+
+/// State kept by the syscall handler: program input and output, the heap break, and
+/// whether the program has exited.
+#[derive(Clone)]
+pub struct SyscallState {
+    /// Text printed by the program that has not been taken by the frontend yet.
+    output: String,
+
+    /// Characters entered by the user that the program has not read yet.
+    input: String,
+
+    /// The read syscall that is waiting for more input, if any.
+    waiting_for_input: Option<InputRequest>,
+
+    exit_code: Option<i32>,
+
+    /// The first address past memory allocated with sbrk, once sbrk has been used.
+    heap_break: Option<usize>,
+}
+
+impl SyscallState {
+    pub fn new() -> SyscallState {
+        SyscallState {
+            output: String::new(),
+            input: String::new(),
+            waiting_for_input: None,
+            exit_code: None,
+            heap_break: None,
+        }
+    }
+}
+
+impl VM {
+    /// Take the text printed by the program since the last call.
+    pub fn take_output(&mut self) -> String {
+        std::mem::take(&mut self.syscalls.output)
+    }
+
+    /// Provide input to the program's read syscalls.
+    pub fn push_input(&mut self, input: &str) {
+        self.syscalls.input.push_str(input);
+        self.syscalls.waiting_for_input = None;
+    }
+
+    /// Return the read syscall waiting for input, if the program is blocked on one. The
+    /// syscall is executed again once input is provided with `push_input`.
+    pub fn get_input_request(&self) -> Option<InputRequest> {
+        self.syscalls.waiting_for_input
+    }
+
+    /// Return the exit code of the program, if it has exited.
+    pub fn get_exit_code(&self) -> Option<i32> {
+        self.syscalls.exit_code
+    }
+
+    /// Copy the syscall state, except for output that has not been taken yet.
+    pub(super) fn save_syscall_state(&self) -> SyscallState {
+        SyscallState {
+            output: String::new(),
+            ..self.syscalls.clone()
+        }
+    }
+
+    /// Restore a copy made by `save_syscall_state`, keeping output that has not been taken
+    /// yet, as it cannot be unprinted.
+    pub(super) fn restore_syscall_state(&mut self, state: SyscallState) {
+        let output = self.take_output();
+        self.syscalls = state;
+        self.syscalls.output = output;
+    }
+
+    /// Execute the syscall selected by $v0.
+    pub(super) fn execute_syscall(&mut self) -> Result<Option<Trap>, RuntimeError> {
+        let service = self.get_v0();
+
+        match service {
+            1 => self.print(&(self.get_a0() as i32).to_string()),
+            2 => self.print(&format_float(self.fpu.get_float(12)?)),
+            3 => self.print(&format_float(self.fpu.get_double(12)?)),
+            4 => {
+                let text = self.read_string(self.get_a0() as usize)?;
+                self.print(&text);
+            }
+            5 => {
+                if let Some(line) = self.take_input_line(InputRequest::Integer) {
+                    let value = parse_input::<i32>(&line, service)?;
+                    self.set_v0(value as u32);
+                }
+            }
+            6 => {
+                if let Some(line) = self.take_input_line(InputRequest::Float) {
+                    let value = parse_input::<f32>(&line, service)?;
+                    self.fpu.set_float(0, value)?;
+                }
+            }
+            7 => {
+                if let Some(line) = self.take_input_line(InputRequest::Double) {
+                    let value = parse_input::<f64>(&line, service)?;
+                    self.fpu.set_double(0, value)?;
+                }
+            }
+            8 => {
+                let buffer = self.get_a0() as usize;
+                let max_length = self.get_a1();
+
+                if let Some(line) = self.take_input_line(InputRequest::String { max_length }) {
+                    self.write_input_string(buffer, max_length, &line)?;
+                }
+            }
+            9 => {
+                let address = self.sbrk(self.get_a0())?;
+                self.set_v0(address as u32);
+            }
+            10 => self.syscalls.exit_code = Some(0),
+            11 => self.print(&(self.get_a0() as u8 as char).to_string()),
+            12 => {
+                if self.syscalls.input.is_empty() {
+                    self.wait_for_input(InputRequest::Character);
+                } else {
+                    let c = self.syscalls.input.remove(0);
+                    self.set_v0(c as u32);
+                }
+            }
+            17 => self.syscalls.exit_code = Some(self.get_a0() as i32),
+            _ => {
+                return Err(RuntimeError::new(
+                    FatalErrorType::InvalidSyscall,
+                    format!("Invalid or unimplemented syscall service {}", service),
+                ))
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn print(&mut self, text: &str) {
+        self.syscalls.output.push_str(text);
+    }
+
+    /// Read the null-terminated string at the given address.
+    fn read_string(&self, address: usize) -> Result<String, RuntimeError> {
+        let mut text = String::new();
+        let mut address = address;
+
+        loop {
+            let byte = self.memory.get_byte(address)?;
+
+            if byte == 0 {
+                return Ok(text);
+            }
+
+            text.push(byte as char);
+            address += 1;
+        }
+    }
+
+    /// Take the next line of input, without its line break. If a full line has not been
+    /// entered yet, start waiting for input and return None.
+    fn take_input_line(&mut self, request: InputRequest) -> Option<String> {
+        match self.syscalls.input.find('\n') {
+            Some(end) => {
+                let line: String = self.syscalls.input.drain(..=end).collect();
+                Some(line.trim_end_matches(['\r', '\n']).to_owned())
+            }
+            None => {
+                self.wait_for_input(request);
+                None
+            }
+        }
+    }
+
+    /// Block on the current syscall, so that it runs again once input is provided.
+    fn wait_for_input(&mut self, request: InputRequest) {
+        self.syscalls.waiting_for_input = Some(request);
+        self.set_pc(self.get_pc() - 4);
+    }
+
+    /// Write a line read by the read string syscall to memory. As in MARS, at most
+    /// `max_length` - 1 characters are written, followed by a line break if there is room
+    /// and then a null terminator.
+    fn write_input_string(
+        &mut self,
+        buffer: usize,
+        max_length: u32,
+        line: &str,
+    ) -> Result<(), RuntimeError> {
+        if max_length < 1 {
+            return Ok(());
+        }
+
+        let mut bytes: Vec<u8> = line.chars().map(|c| c as u8).collect();
+        bytes.push(b'\n');
+        bytes.truncate(max_length as usize - 1);
+        bytes.push(0);
+
+        for (i, byte) in bytes.iter().enumerate() {
+            self.memory.set_byte(buffer + i, *byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Allocate the given number of bytes on the heap, and return the address of the
+    /// allocated memory.
+    fn sbrk(&mut self, size: u32) -> Result<usize, RuntimeError> {
+        let heap = self.memory.segment_by_name("heap").ok_or_else(|| {
+            RuntimeError::new(
+                FatalErrorType::InvalidSyscall,
+                String::from("sbrk requires a heap segment"),
+            )
+        })?;
+        let heap_low = heap.get_low_address();
+        let heap_high = heap.get_high_address();
+
+        let address = self.syscalls.heap_break.unwrap_or(heap_low);
+        // Keep allocations word aligned.
+        let end = address + (size as usize).next_multiple_of(4);
+
+        if end > heap_high + 1 {
+            return Err(RuntimeError::new(
+                FatalErrorType::InvalidSyscall,
+                format!(
+                    "sbrk of {} bytes exceeds the heap, which ends at {:#010x}",
+                    size, heap_high
+                ),
+            ));
+        }
+
+        self.syscalls.heap_break = Some(end);
+
+        Ok(address)
+    }
+}
+
+/// Parse a line of input for a read syscall.
+fn parse_input<T: std::str::FromStr>(line: &str, service: u32) -> Result<T, RuntimeError> {
+    line.trim().parse().map_err(|_| {
+        RuntimeError::new(
+            FatalErrorType::InvalidSyscall,
+            format!("Invalid input \"{}\" (syscall {})", line, service),
+        )
+    })
+}
+
+/// Format a floating point number as MARS prints it, such as "1.0", "0.1" or "1.5E10".
+fn format_float<T: Into<f64> + Display + LowerExp + Copy>(value: T) -> String {
+    let as_double: f64 = value.into();
+
+    if as_double.is_nan() {
+        return String::from("NaN");
+    }
+
+    if as_double.is_infinite() {
+        return String::from(if as_double > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        });
+    }
+
+    let magnitude = as_double.abs();
+
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        let text = value.to_string();
+
+        if text.contains('.') {
+            text
+        } else {
+            text + ".0"
+        }
+    } else {
+        let text = format!("{:e}", value);
+        let (mantissa, exponent) = text.split_once('e').unwrap();
+
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, exponent)
+        } else {
+            format!("{}.0E{}", mantissa, exponent)
+        }
+    }
+}
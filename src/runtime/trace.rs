@@ -0,0 +1,537 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::mips::disassembler::disassemble;
+use crate::mips::instruction::InstructionData;
+use crate::runtime::fpu::Coprocessor1;
+use crate::runtime::journal::Change;
+use crate::runtime::register_aliases::Register;
+use crate::runtime::vm::VM;
+
+/// The first bytes of a binary trace, followed by a format version.
+const BINARY_MAGIC: &[u8; 4] = b"JTRC";
+const BINARY_VERSION: u8 = 1;
+
+/// How a trace is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One tab-separated line per instruction, with its disassembly.
+    Text,
+    /// A compact encoding without disassembly. Numbers are little-endian.
+    Binary,
+}
+
+/// A change made by an executed instruction, with the new value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Effect {
+    Register(u8, u32),
+    Hi(u32),
+    Lo(u32),
+    Memory(u32, Vec<u8>),
+    /// A floating point register, as raw bits.
+    Float(u8, u32),
+    /// The floating point control and status register, which holds the condition flags.
+    Fcsr(u32),
+}
+
+/// An executed instruction and what it changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub pc: u32,
+    pub word: u32,
+    pub effects: Vec<Effect>,
+}
+
+/// Shows a step as in a text trace, without the disassembly.
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x}  {:08x}", self.pc, self.word)?;
+
+        for effect in &self.effects {
+            write!(f, "  {}", effect)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Effect::Register(register, value) => {
+                write!(f, "{}={:#010x}", Register::General(*register), value)
+            }
+            Effect::Hi(value) => write!(f, "hi={:#010x}", value),
+            Effect::Lo(value) => write!(f, "lo={:#010x}", value),
+            Effect::Float(register, value) => {
+                write!(f, "{}={:#010x}", Register::Float(*register), value)
+            }
+            Effect::Fcsr(value) => write!(f, "fcsr={:#010x}", value),
+            Effect::Memory(address, bytes) => {
+                write!(f, "[{:#010x}]=", address)?;
+
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Effect {
+    fn parse(text: &str) -> Result<Effect, String> {
+        let invalid = || format!("Invalid effect \"{}\"", text);
+        let (target, value) = text.split_once('=').ok_or_else(invalid)?;
+
+        if let Some(address) = target
+            .strip_prefix('[')
+            .and_then(|target| target.strip_suffix(']'))
+        {
+            let bytes = (0..value.len())
+                .step_by(2)
+                .map(|i| {
+                    value
+                        .get(i..i + 2)
+                        .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid)?;
+
+            return Ok(Effect::Memory(
+                parse_hex(address).ok_or_else(invalid)?,
+                bytes,
+            ));
+        }
+
+        let value = parse_hex(value).ok_or_else(invalid)?;
+
+        if target == "fcsr" {
+            return Ok(Effect::Fcsr(value));
+        }
+
+        match Register::parse(target) {
+            Some(Register::General(register)) => Ok(Effect::Register(register, value)),
+            Some(Register::Float(register)) => Ok(Effect::Float(register, value)),
+            Some(Register::Hi) => Ok(Effect::Hi(value)),
+            Some(Register::Lo) => Ok(Effect::Lo(value)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+/// Writes a step to a trace file for each executed instruction.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: TraceFormat,
+    /// The first error writing the trace. Tracing stops once there is one.
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(mut out: Box<dyn Write>, format: TraceFormat) -> Tracer {
+        let error = match format {
+            TraceFormat::Binary => out
+                .write_all(BINARY_MAGIC)
+                .and_then(|_| out.write_all(&[BINARY_VERSION]))
+                .err(),
+            TraceFormat::Text => None,
+        };
+
+        Tracer { out, format, error }
+    }
+
+    /// Write the step of the instruction the VM just executed.
+    pub fn record(&mut self, step: &TraceStep, disassembly: impl FnOnce() -> String) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = match self.format {
+            TraceFormat::Text => write_text_step(&mut self.out, step, &disassembly()),
+            TraceFormat::Binary => write_binary_step(&mut self.out, step),
+        };
+
+        self.error = result.err();
+    }
+
+    /// Flush the trace, and return the first error writing it, if any.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+}
+
+fn write_text_step(out: &mut dyn Write, step: &TraceStep, disassembly: &str) -> io::Result<()> {
+    write!(
+        out,
+        "{:#010x}\t{:08x}\t{}\t",
+        step.pc, step.word, disassembly
+    )?;
+
+    let effects: Vec<String> = step.effects.iter().map(Effect::to_string).collect();
+    writeln!(out, "{}", effects.join(" "))
+}
+
+fn write_binary_step(out: &mut dyn Write, step: &TraceStep) -> io::Result<()> {
+    out.write_all(&step.pc.to_le_bytes())?;
+    out.write_all(&step.word.to_le_bytes())?;
+    out.write_all(&[step.effects.len() as u8])?;
+
+    for effect in &step.effects {
+        match effect {
+            Effect::Register(register, value) => {
+                out.write_all(&[0, *register])?;
+                out.write_all(&value.to_le_bytes())?;
+            }
+            Effect::Hi(value) => {
+                out.write_all(&[1])?;
+                out.write_all(&value.to_le_bytes())?;
+            }
+            Effect::Lo(value) => {
+                out.write_all(&[2])?;
+                out.write_all(&value.to_le_bytes())?;
+            }
+            Effect::Memory(address, bytes) => {
+                out.write_all(&[3])?;
+                out.write_all(&address.to_le_bytes())?;
+                out.write_all(&[bytes.len() as u8])?;
+                out.write_all(bytes)?;
+            }
+            Effect::Float(register, value) => {
+                out.write_all(&[4, *register])?;
+                out.write_all(&value.to_le_bytes())?;
+            }
+            Effect::Fcsr(value) => {
+                out.write_all(&[5])?;
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a trace in either format, telling them apart by the binary header.
+pub fn read_trace(data: &[u8]) -> Result<Vec<TraceStep>, String> {
+    match data.strip_prefix(BINARY_MAGIC) {
+        Some(rest) => read_binary_trace(rest),
+        None => {
+            let text = std::str::from_utf8(data).map_err(|_| String::from("Invalid text trace"))?;
+            read_text_trace(text)
+        }
+    }
+}
+
+fn read_text_trace(text: &str) -> Result<Vec<TraceStep>, String> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            let invalid = || format!("Invalid trace line {}: \"{}\"", i + 1, line);
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            if fields.len() != 4 {
+                return Err(invalid());
+            }
+
+            Ok(TraceStep {
+                pc: parse_hex(fields[0]).ok_or_else(invalid)?,
+                word: parse_hex(fields[1]).ok_or_else(invalid)?,
+                effects: fields[3]
+                    .split_whitespace()
+                    .map(Effect::parse)
+                    .collect::<Result<_, _>>()?,
+            })
+        })
+        .collect()
+}
+
+fn read_binary_trace(data: &[u8]) -> Result<Vec<TraceStep>, String> {
+    let mut reader = BinaryReader { data, position: 0 };
+
+    if reader.byte()? != BINARY_VERSION {
+        return Err(String::from("Unsupported binary trace version"));
+    }
+
+    let mut steps = vec![];
+
+    while reader.position < data.len() {
+        let pc = reader.word()?;
+        let word = reader.word()?;
+        let count = reader.byte()?;
+
+        let effects = (0..count)
+            .map(|_| match reader.byte()? {
+                0 => Ok(Effect::Register(reader.byte()?, reader.word()?)),
+                1 => Ok(Effect::Hi(reader.word()?)),
+                2 => Ok(Effect::Lo(reader.word()?)),
+                3 => {
+                    let address = reader.word()?;
+                    let length = reader.byte()? as usize;
+                    Ok(Effect::Memory(address, reader.bytes(length)?.to_vec()))
+                }
+                4 => Ok(Effect::Float(reader.byte()?, reader.word()?)),
+                5 => Ok(Effect::Fcsr(reader.word()?)),
+                tag => Err(format!("Invalid effect tag {} in binary trace", tag)),
+            })
+            .collect::<Result<_, String>>()?;
+
+        steps.push(TraceStep { pc, word, effects });
+    }
+
+    Ok(steps)
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(|| String::from("Binary trace ends in the middle of a step"))?;
+
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+/// Where two traces first differ.
+pub enum Divergence {
+    /// The steps at this index differ.
+    Step(usize),
+    /// One trace ends after this many steps, while the other goes on.
+    Length(usize),
+}
+
+/// Find the first step at which two traces differ, if any.
+pub fn first_divergence(a: &[TraceStep], b: &[TraceStep]) -> Option<Divergence> {
+    match a.iter().zip(b).position(|(a, b)| a != b) {
+        Some(index) => Some(Divergence::Step(index)),
+        None if a.len() != b.len() => Some(Divergence::Length(a.len().min(b.len()))),
+        None => None,
+    }
+}
+
+impl VM {
+    /// Record the instruction that was just executed from `address` in the trace, if tracing
+    /// is enabled. `fpu` is the state of the FPU before the instruction, as FPU changes are
+    /// found by comparing it with the current state.
+    pub(super) fn trace_step(
+        &mut self,
+        address: usize,
+        instruction: &InstructionData,
+        fpu: &Coprocessor1,
+    ) {
+        if self.tracer.is_none() {
+            return;
+        }
+
+        let mut effects: Vec<Effect> = self
+            .get_last_changes()
+            .iter()
+            .filter_map(|change| match change {
+                Change::Register(register, _) => self
+                    .get_register(*register)
+                    .ok()
+                    .map(|value| Effect::Register(*register, value)),
+                Change::Hi(_) => Some(Effect::Hi(self.get_hi())),
+                Change::Lo(_) => Some(Effect::Lo(self.get_lo())),
+                Change::Memory(..) => None,
+            })
+            .collect();
+
+        for register in 0..32 {
+            let value = self.fpu.get_register(register).unwrap_or(0);
+
+            if fpu.get_register(register).unwrap_or(0) != value {
+                effects.push(Effect::Float(register, value));
+            }
+        }
+
+        if fpu.get_fcsr() != self.fpu.get_fcsr() {
+            effects.push(Effect::Fcsr(self.fpu.get_fcsr()));
+        }
+
+        for &(low, size) in self.memory.get_last_writes() {
+            let bytes = (low..low + size)
+                .map(|address| self.memory.get_byte(address).unwrap_or(0))
+                .collect();
+
+            effects.push(Effect::Memory(low as u32, bytes));
+        }
+
+        let step = TraceStep {
+            pc: address as u32,
+            word: self.memory.get_word(address).unwrap_or(0),
+            effects,
+        };
+
+        let disassembly = || {
+            disassemble(instruction, address as u32, |target| {
+                self.debug_info.symbols_at(target as usize).next()
+            })
+            .to_string()
+        };
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.record(&step, disassembly);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::runtime::loader::{vm_running, DATA};
+    use crate::runtime::stepping::{Run, RunTarget};
+
+    /// A writer whose contents can still be read after it is handed to a tracer.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Trace `words` run with `$t1` pointing at the data segment, and read the trace back.
+    fn traced(words: &[u32], format: TraceFormat) -> Vec<TraceStep> {
+        let buffer = SharedBuffer::default();
+        let mut vm = vm_running(words);
+        vm.tracer = Some(Tracer::new(Box::new(buffer.clone()), format));
+
+        vm.run(&mut Run::new(RunTarget::Continue), 100).unwrap();
+        vm.tracer.as_mut().unwrap().finish().unwrap();
+
+        let data = buffer.0.borrow();
+        read_trace(&data).unwrap()
+    }
+
+    /// `li $t0, 5; sw $t0, 0($t1); mult $t0, $t0`
+    const PROGRAM: [u32; 3] = [0x24080005, 0xad280000, 0x01080018];
+
+    #[test]
+    fn traces_read_back_as_written() {
+        let expected = vec![
+            TraceStep {
+                pc: 0x00400000,
+                word: 0x24080005,
+                effects: vec![Effect::Register(8, 5)],
+            },
+            TraceStep {
+                pc: 0x00400004,
+                word: 0xad280000,
+                effects: vec![Effect::Memory(DATA as u32, vec![0, 0, 0, 5])],
+            },
+            TraceStep {
+                pc: 0x00400008,
+                word: 0x01080018,
+                effects: vec![Effect::Lo(25)],
+            },
+        ];
+
+        assert_eq!(traced(&PROGRAM, TraceFormat::Text), expected);
+        assert_eq!(traced(&PROGRAM, TraceFormat::Binary), expected);
+    }
+
+    #[test]
+    fn fpu_effects_read_back_as_written() {
+        // li $t0, 5; mtc1 $t0, $f2; c.eq.s $f2, $f2
+        let program = [0x24080005, 0x44881000, 0x46021032];
+        let expected = vec![
+            TraceStep {
+                pc: 0x00400000,
+                word: 0x24080005,
+                effects: vec![Effect::Register(8, 5)],
+            },
+            TraceStep {
+                pc: 0x00400004,
+                word: 0x44881000,
+                effects: vec![Effect::Float(2, 5)],
+            },
+            TraceStep {
+                pc: 0x00400008,
+                word: 0x46021032,
+                effects: vec![Effect::Fcsr(1 << 23)],
+            },
+        ];
+
+        assert_eq!(traced(&program, TraceFormat::Text), expected);
+        assert_eq!(traced(&program, TraceFormat::Binary), expected);
+    }
+
+    #[test]
+    fn text_and_binary_traces_compare_equal() {
+        let text = traced(&PROGRAM, TraceFormat::Text);
+        let binary = traced(&PROGRAM, TraceFormat::Binary);
+
+        assert!(first_divergence(&text, &binary).is_none());
+    }
+
+    #[test]
+    fn first_divergence_finds_the_first_different_step() {
+        let original = traced(&PROGRAM, TraceFormat::Binary);
+        // Stores 6 rather than 5.
+        let changed = traced(&[0x24080006, 0xad280000, 0x01080018], TraceFormat::Text);
+        let shorter = traced(&PROGRAM[..2], TraceFormat::Text);
+
+        assert!(matches!(
+            first_divergence(&original, &changed),
+            Some(Divergence::Step(0))
+        ));
+        assert!(matches!(
+            first_divergence(&original, &shorter),
+            Some(Divergence::Length(2))
+        ));
+        assert!(matches!(
+            first_divergence(&shorter, &original),
+            Some(Divergence::Length(2))
+        ));
+    }
+
+    #[test]
+    fn malformed_traces_are_rejected() {
+        assert!(read_trace(b"JTRC\x01\x00").is_err());
+        assert!(read_trace(b"JTRC\x09").is_err());
+        assert!(read_trace(b"0x00400000\t24080005\tli $t0, 5").is_err());
+        assert_eq!(
+            read_trace(b"0x00400000\t24080005\tli $t0, 5\t$t0=zz"),
+            Err(String::from("Invalid effect \"$t0=zz\""))
+        );
+        // Blank lines still count towards the line numbers in errors.
+        assert_eq!(
+            read_trace(b"\n\n0x00400000\t24080005"),
+            Err(String::from(
+                "Invalid trace line 3: \"0x00400000\t24080005\""
+            ))
+        );
+    }
+}
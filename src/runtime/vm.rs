@@ -1,8 +1,19 @@
+use crate::runtime::breakpoints::Breakpoint;
+use crate::runtime::coverage::Coverage;
+use crate::runtime::cp0::Coprocessor0;
+use crate::runtime::debug_info::DebugInfo;
+use crate::runtime::fpu::Coprocessor1;
+use crate::runtime::journal::{Change, Journal};
 use crate::runtime::memory::MemoryMap;
 use crate::runtime::memory::MemorySegment;
 use crate::runtime::memory::SegmentDirection;
+use crate::runtime::profiler::Profiler;
+use crate::runtime::statistics::Statistics;
+use crate::runtime::syscalls::SyscallState;
+use crate::runtime::trace::Tracer;
 
-use super::errors::{RuntimeError, FatalErrorType};
+use super::errors::{FatalErrorType, RuntimeError, Warning};
+use crate::mips::instruction::Isa;
 
 pub struct MemoryLayout {
     pub text_low: usize,
@@ -31,15 +42,101 @@ impl MemoryLayout {
     }
 }
 
+/// Settings that change how the VM interprets a program.
+pub struct VMOptions {
+    /// The instruction set accepted by the VM. Instructions from later revisions are
+    /// treated as illegal instructions.
+    pub isa: Isa,
+    /// Execute the instruction after a branch or jump before control is transferred, as
+    /// real MIPS processors do. Off by default, as in MARS.
+    ///
+    /// Programs are loaded already assembled, so whether their delay slots hold nops or
+    /// useful instructions is up to the assembler that built them. `.set noreorder` and
+    /// `.set reorder` have no meaning here.
+    pub delayed_branching: bool,
+    /// The number of executed instructions that can be undone. Zero disables the journal.
+    pub journal_capacity: usize,
+    /// Hide the result of a load from the instruction after it, as MIPS-I processors do, and
+    /// warn when that instruction reads the loaded register.
+    pub load_delay: bool,
+}
+
+impl Default for VMOptions {
+    fn default() -> Self {
+        VMOptions {
+            isa: Isa::Mips32R2,
+            delayed_branching: false,
+            journal_capacity: 2000,
+            load_delay: false,
+        }
+    }
+}
+
+/// A load whose result is not yet visible, because the next instruction is in its delay slot.
+#[derive(Debug, Clone, Copy)]
+pub struct DelayedLoad {
+    pub register: u8,
+    pub value: u32,
+    /// The address of the load instruction.
+    pub address: usize,
+    /// Whether the instruction in the delay slot has read the register. Only the first read
+    /// is reported.
+    pub hazard: bool,
+}
+
 pub struct VM {
     registers: [u32; 32],
     pub memory: MemoryMap,
+    pub fpu: Coprocessor1,
+    pub cp0: Coprocessor0,
+    pub syscalls: SyscallState,
+    pub options: VMOptions,
+    pub debug_info: DebugInfo,
+    /// Counts of the instructions executed since the program was loaded. Stepping back takes
+    /// back the count of each undone instruction.
+    pub statistics: Statistics,
+    /// Attributes executed instructions to lines and functions, when profiling is enabled.
+    /// Profiles only grow: stepping back does not take back what was recorded.
+    pub profiler: Option<Profiler>,
+    /// The instructions and branch outcomes executed, when coverage is enabled. Stepping back
+    /// leaves undone instructions covered, as they did execute.
+    pub coverage: Option<Coverage>,
+    /// Writes each executed instruction and its effects to a trace, when tracing is enabled.
+    pub tracer: Option<Tracer>,
 
     /// The program counter.
     pc: usize,
 
     hi: u32,
     lo: u32,
+
+    /// The address of the word loaded by the last `ll`, while the link bit is set.
+    link: Option<usize>,
+
+    /// The target of a taken branch whose delay slot has not yet executed.
+    delayed_branch: Option<usize>,
+
+    /// The load issued by the current instruction, which completes after the next one.
+    delayed_load: Option<DelayedLoad>,
+    /// The load whose delay slot is the current instruction.
+    load_delay_slot: Option<DelayedLoad>,
+
+    /// Warnings raised since the frontend last took them.
+    warnings: Vec<Warning>,
+
+    /// The value each general purpose register, HI and LO held before its most recent change.
+    previous_registers: [u32; 32],
+    previous_hi: u32,
+    previous_lo: u32,
+    /// The registers changed by the executing or most recently executed instruction, with
+    /// the values they held before.
+    last_changes: Vec<Change>,
+
+    pub(super) breakpoints: Vec<Breakpoint>,
+    pub(super) journal: Journal,
+    /// The address execution last stopped at, until the next instruction executes. A run
+    /// that starts here does not stop at a breakpoint on it again.
+    pub(super) stopped_at: Option<usize>,
 }
 
 impl VM {
@@ -61,7 +158,7 @@ impl VM {
             layout.heap_low - layout.data_low,
             false,
             SegmentDirection::Up,
-            true,
+            false,
         ));
 
         memory.add_segment(MemorySegment::new(
@@ -94,9 +191,30 @@ impl VM {
         VM {
             registers: [0; 32],
             memory,
+            fpu: Coprocessor1::new(),
+            cp0: Coprocessor0::new(),
+            syscalls: SyscallState::new(),
+            options: VMOptions::default(),
+            debug_info: DebugInfo::new(),
+            statistics: Statistics::new(),
+            profiler: None,
+            coverage: None,
+            tracer: None,
             pc: layout.text_low,
             hi: 0,
             lo: 0,
+            link: None,
+            delayed_branch: None,
+            delayed_load: None,
+            load_delay_slot: None,
+            warnings: vec![],
+            previous_registers: [0; 32],
+            previous_hi: 0,
+            previous_lo: 0,
+            last_changes: vec![],
+            breakpoints: vec![],
+            journal: Journal::new(),
+            stopped_at: None,
         }
     }
 
@@ -118,6 +236,14 @@ impl VM {
             ));
         }
 
+        let old = self.registers[register as usize];
+        self.journal.record(Change::Register(register, old));
+
+        if old != value {
+            self.previous_registers[register as usize] = old;
+            self.last_changes.push(Change::Register(register, old));
+        }
+
         self.registers[register as usize] = value;
 
         Ok(())
@@ -150,6 +276,13 @@ impl VM {
     }
 
     pub fn set_hi(&mut self, value: u32) {
+        self.journal.record(Change::Hi(self.hi));
+
+        if self.hi != value {
+            self.previous_hi = self.hi;
+            self.last_changes.push(Change::Hi(self.hi));
+        }
+
         self.hi = value;
     }
 
@@ -158,6 +291,116 @@ impl VM {
     }
 
     pub fn set_lo(&mut self, value: u32) {
+        self.journal.record(Change::Lo(self.lo));
+
+        if self.lo != value {
+            self.previous_lo = self.lo;
+            self.last_changes.push(Change::Lo(self.lo));
+        }
+
         self.lo = value;
     }
+
+    /// Get the address linked by `ll`, or None if the link bit is clear.
+    pub fn get_link(&self) -> Option<usize> {
+        self.link
+    }
+
+    pub fn set_link(&mut self, address: Option<usize>) {
+        self.link = address;
+    }
+
+    /// Get the target of a branch waiting on its delay slot, if any.
+    pub fn get_delayed_branch(&self) -> Option<usize> {
+        self.delayed_branch
+    }
+
+    pub fn set_delayed_branch(&mut self, target: Option<usize>) {
+        self.delayed_branch = target;
+    }
+
+    /// Take the target of a branch waiting on its delay slot, leaving none pending.
+    pub fn take_delayed_branch(&mut self) -> Option<usize> {
+        self.delayed_branch.take()
+    }
+
+    /// Get the load issued by the previous instruction, if it has not completed yet.
+    pub fn get_delayed_load(&self) -> Option<DelayedLoad> {
+        self.delayed_load
+    }
+
+    pub fn set_delayed_load(&mut self, load: Option<DelayedLoad>) {
+        self.delayed_load = load;
+    }
+
+    /// Take the load issued by the previous instruction, leaving none pending.
+    pub fn take_delayed_load(&mut self) -> Option<DelayedLoad> {
+        self.delayed_load.take()
+    }
+
+    /// Get the load whose delay slot is being executed, if any.
+    pub fn get_load_delay_slot(&self) -> Option<DelayedLoad> {
+        self.load_delay_slot
+    }
+
+    pub fn set_load_delay_slot(&mut self, load: Option<DelayedLoad>) {
+        self.load_delay_slot = load;
+    }
+
+    pub fn warn(&mut self, message: String) {
+        self.warnings.push(Warning::new(message));
+    }
+
+    /// Take the warnings raised since the last call.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Get the value the given register held before its most recent change.
+    pub fn get_previous_register(&self, register: u8) -> Result<u32, RuntimeError> {
+        self.get_register(register)?;
+
+        Ok(self.previous_registers[register as usize])
+    }
+
+    pub fn get_previous_hi(&self) -> u32 {
+        self.previous_hi
+    }
+
+    pub fn get_previous_lo(&self) -> u32 {
+        self.previous_lo
+    }
+
+    /// Get the registers changed by the most recently executed instruction, with the values
+    /// they held before.
+    pub fn get_last_changes(&self) -> &Vec<Change> {
+        &self.last_changes
+    }
+
+    pub fn clear_last_changes(&mut self) {
+        self.last_changes.clear();
+    }
+
+    /// Carry the options, byte order, debug information, breakpoints, watchpoints, profiling,
+    /// coverage and trace over from `previous`, such as when the program is reloaded.
+    /// Breakpoint hit counts, profiles and coverage start again from zero, while the trace
+    /// carries on.
+    pub fn keep_debugging_state(&mut self, previous: VM) {
+        self.options = previous.options;
+        self.memory.set_endianness(previous.memory.get_endianness());
+        self.debug_info = previous.debug_info;
+        self.memory.watchpoints = previous.memory.watchpoints;
+        self.breakpoints = previous.breakpoints;
+
+        if previous.profiler.is_some() {
+            self.profiler = Some(Profiler::new());
+        }
+
+        self.coverage = previous.coverage.map(|coverage| coverage.cleared());
+        self.tracer = previous.tracer;
+
+        for breakpoint in &mut self.breakpoints {
+            breakpoint.hits = 0;
+        }
+    }
 }
@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+
+use crate::runtime::vm::VM;
+
+/// A kind of memory access that a watchpoint can stop on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    /// A write that changes the value in memory.
+    Change,
+}
+
+impl WatchAccess {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchAccess::Read => "read",
+            WatchAccess::Write => "write",
+            WatchAccess::Change => "change",
+        }
+    }
+}
+
+/// Stops execution when an instruction accesses memory in the range from `low` to `high`,
+/// inclusive.
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub low: usize,
+    pub high: usize,
+    pub on_read: bool,
+    pub on_write: bool,
+    pub on_change: bool,
+}
+
+impl Watchpoint {
+    /// A watchpoint on writes to the given range.
+    pub fn new(low: usize, high: usize) -> Watchpoint {
+        Watchpoint {
+            low,
+            high,
+            on_read: false,
+            on_write: true,
+            on_change: false,
+        }
+    }
+
+    fn overlaps(&self, address: usize, size: usize) -> bool {
+        address <= self.high && self.low < address + size
+    }
+
+    fn triggers_on(&self, access: WatchAccess) -> bool {
+        match access {
+            WatchAccess::Read => self.on_read,
+            WatchAccess::Write => self.on_write,
+            WatchAccess::Change => self.on_change,
+        }
+    }
+}
+
+/// An access that triggered a watchpoint.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchpointHit {
+    /// The index of the watchpoint that was triggered.
+    pub watchpoint: usize,
+    pub access: WatchAccess,
+    pub address: usize,
+    pub size: usize,
+}
+
+/// The watchpoints on a memory map, and the accesses that have triggered them.
+///
+/// Watchpoints are only checked while armed, so that the frontend can inspect memory without
+/// triggering them. Reads do not otherwise need mutable access to memory, so hits are
+/// collected through a `RefCell`.
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    armed: bool,
+    hits: RefCell<Vec<WatchpointHit>>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        Watchpoints {
+            watchpoints: vec![],
+            armed: false,
+            hits: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn get_all(&self) -> &Vec<Watchpoint> {
+        &self.watchpoints
+    }
+
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Remove the watchpoint at the given index, if there is one.
+    pub fn remove(&mut self, index: usize) -> Option<Watchpoint> {
+        if index < self.watchpoints.len() {
+            Some(self.watchpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    pub fn arm(&mut self) {
+        self.armed = true;
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = false;
+    }
+
+    /// Whether an instruction is executing, so that its accesses are being checked.
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    /// Take the hits recorded since the last call.
+    pub fn take_hits(&mut self) -> Vec<WatchpointHit> {
+        self.hits.take()
+    }
+
+    /// Return true if a write of `size` bytes at `address` could trigger a change watchpoint,
+    /// so that the caller knows to compare the old and new values.
+    pub(super) fn watches_changes(&self, address: usize, size: usize) -> bool {
+        self.armed
+            && self
+                .watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.on_change && watchpoint.overlaps(address, size))
+    }
+
+    /// Record an access of `size` bytes at `address`, if it triggers any watchpoints.
+    pub(super) fn check(&self, access: WatchAccess, address: usize, size: usize) {
+        if !self.armed {
+            return;
+        }
+
+        for (index, watchpoint) in self.watchpoints.iter().enumerate() {
+            if watchpoint.triggers_on(access) && watchpoint.overlaps(address, size) {
+                self.hits.borrow_mut().push(WatchpointHit {
+                    watchpoint: index,
+                    access,
+                    address,
+                    size,
+                });
+            }
+        }
+    }
+}
+
+impl VM {
+    /// Describe a watchpoint hit caused by the instruction at `address`, for example
+    /// "write of 4 bytes at 0x10010000 by sw at 0x00400008".
+    pub fn describe_watchpoint_hit(&self, hit: &WatchpointHit, address: usize) -> String {
+        let instruction = self
+            .memory
+            .get_word(address)
+            .ok()
+            .and_then(|code| self.decode_instruction(code).ok())
+            .map(|instruction| instruction.base.name)
+            .unwrap_or("???");
+
+        format!(
+            "{} of {} byte{} at {:#010x} by {} at {:#010x}",
+            hit.access.name(),
+            hit.size,
+            if hit.size == 1 { "" } else { "s" },
+            hit.address,
+            instruction,
+            address
+        )
+    }
+}
@@ -1,17 +1,30 @@
 pub mod console;
+pub mod speed;
 
 use crate::{
-    mips::instruction::InstructionArgs,
-    runtime::{register_aliases::*, vm::VM},
+    mips::{disassembler::disassemble, instruction::InstructionFormat},
+    runtime::{
+        breakpoints::BreakpointLocation,
+        cp0::Coprocessor0,
+        expression::Expression,
+        journal::Change,
+        memory::SegmentDirection,
+        register_aliases::{register_name, Register},
+        statistics::InstructionCategory,
+        syscalls::InputRequest,
+        vm::VM,
+    },
 };
 use crossterm::{event, execute, terminal};
-use std::io::{self, Write};
+use speed::RunSpeed;
+use std::io;
+use std::time::Duration;
 use tui::{
-    backend::{self, Backend, CrosstermBackend},
+    backend::{Backend, CrosstermBackend},
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans, Text},
-    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table, Tabs},
     Frame, Terminal,
 };
 
@@ -19,17 +32,214 @@ pub enum VMViewerEvent {
     None,
     Quit,
     TogglePause,
+    Step,
+    StepOver,
+    StepOut,
+    StepBack,
+    RunBackward,
+    /// Reload the program and start again.
+    Reset,
+    ToggleBreakpoint(BreakpointLocation),
+    /// Input entered for the read syscall the program is waiting on.
+    SubmitInput(String),
+    SaveConsole,
+    /// Raise or lower the run speed by one step.
+    Faster,
+    Slower,
 }
 
 pub struct VMState<'a> {
     pub vm: &'a VM,
     pub paused: bool,
     pub halted: bool,
+    pub speed: RunSpeed,
     pub console: &'a console::Console<'a>,
+    pub watch_expressions: &'a [Expression],
+}
+
+/// The set of registers shown in the register panel.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RegisterTab {
+    General,
+    Coprocessor1,
+    Coprocessor0,
+}
+
+impl RegisterTab {
+    const ALL: [RegisterTab; 3] = [
+        RegisterTab::General,
+        RegisterTab::Coprocessor1,
+        RegisterTab::Coprocessor0,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            RegisterTab::General => "GPR",
+            RegisterTab::Coprocessor1 => "COP1",
+            RegisterTab::Coprocessor0 => "COP0",
+        }
+    }
+
+    fn index(&self) -> usize {
+        RegisterTab::ALL.iter().position(|tab| tab == self).unwrap()
+    }
+
+    /// The tab after this one, wrapping around at the end.
+    fn next(&self) -> RegisterTab {
+        RegisterTab::ALL[(self.index() + 1) % RegisterTab::ALL.len()]
+    }
+}
+
+/// The number of instructions shown in the instruction table.
+const INSTRUCTION_ROWS: usize = 10;
+
+/// The number of rows shown in the memory table, and the number of bytes in each.
+const MEMORY_ROWS: usize = 6;
+const MEMORY_ROW_BYTES: usize = 16;
+
+/// The number of lines the console scrolls by for each key press.
+const CONSOLE_SCROLL_LINES: usize = 5;
+
+/// How a word in memory or a register is shown.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ValueFormat {
+    Hex,
+    Signed,
+    Unsigned,
+    Ascii,
+    Float,
+}
+
+impl ValueFormat {
+    /// The formats available in the memory table.
+    const MEMORY: [ValueFormat; 5] = [
+        ValueFormat::Hex,
+        ValueFormat::Signed,
+        ValueFormat::Unsigned,
+        ValueFormat::Ascii,
+        ValueFormat::Float,
+    ];
+
+    /// The formats available in the register tables.
+    const REGISTERS: [ValueFormat; 4] = [
+        ValueFormat::Hex,
+        ValueFormat::Unsigned,
+        ValueFormat::Signed,
+        ValueFormat::Ascii,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            ValueFormat::Hex => "hex",
+            ValueFormat::Signed => "signed",
+            ValueFormat::Unsigned => "unsigned",
+            ValueFormat::Ascii => "ASCII",
+            ValueFormat::Float => "float",
+        }
+    }
+
+    /// The format after this one in `formats`, wrapping around at the end.
+    fn next_of(&self, formats: &[ValueFormat]) -> ValueFormat {
+        let index = formats
+            .iter()
+            .position(|format| format == self)
+            .map_or(0, |index| index + 1);
+
+        formats[index % formats.len()]
+    }
+
+    fn format_word(&self, word: u32) -> String {
+        match self {
+            ValueFormat::Hex => format!("{:#010x}", word),
+            ValueFormat::Signed => format!("{}", word as i32),
+            ValueFormat::Unsigned => format!("{}", word),
+            ValueFormat::Ascii => word.to_be_bytes().iter().map(|&byte| ascii(byte)).collect(),
+            ValueFormat::Float => format!("{}", f32::from_bits(word)),
+        }
+    }
+}
+
+/// Show a byte as a printable ASCII character, or '.' if it is not one.
+fn ascii(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// State of the viewer itself, such as selections, which is kept between updates.
+struct ViewState {
+    register_tab: RegisterTab,
+    /// The selected row of the instruction table, counted from the PC.
+    instruction_cursor: usize,
+    /// The address of the first row of the memory table, or None to show the data segment.
+    memory_address: Option<usize>,
+    memory_format: ValueFormat,
+    /// The format of each register tab, in the order of `RegisterTab::ALL`.
+    register_formats: [ValueFormat; 3],
+    /// The address being typed after pressing G, if any.
+    address_input: Option<String>,
+    /// The input being typed for a read syscall.
+    console_input: console::LineEditor,
+    /// How many lines the console is scrolled up from the bottom. At 0 it follows new output.
+    console_scroll: usize,
+}
+
+impl ViewState {
+    /// The format of the register tab being shown.
+    fn register_format(&self) -> ValueFormat {
+        self.register_formats[self.register_tab.index()]
+    }
+
+    fn next_register_format(&mut self) {
+        let format = &mut self.register_formats[self.register_tab.index()];
+        *format = format.next_of(&ValueFormat::REGISTERS);
+    }
+
+    /// The address of the first row of the memory table.
+    fn get_memory_address(&self, vm: &VM) -> usize {
+        self.memory_address.unwrap_or_else(|| {
+            vm.memory
+                .segment_by_name("data")
+                .map_or(0, |segment| segment.get_low_address())
+        })
+    }
+
+    /// Show the memory table from `address`, rounded down to the start of a row.
+    fn set_memory_address(&mut self, address: usize) {
+        self.memory_address = Some(address - address % MEMORY_ROW_BYTES);
+    }
+
+    /// Show the start of the segment after the one shown, or for a segment that grows down,
+    /// its end.
+    fn next_segment(&mut self, vm: &VM) {
+        let segments = vm.memory.get_segments();
+        let address = self.get_memory_address(vm);
+
+        let index = segments
+            .iter()
+            .position(|segment| {
+                (segment.get_low_address()..=segment.get_high_address()).contains(&address)
+            })
+            .map_or(0, |index| (index + 1) % segments.len());
+
+        if let Some(segment) = segments.get(index) {
+            let address = match segment.direction {
+                SegmentDirection::Up => segment.get_low_address(),
+                SegmentDirection::Down => (segment.get_high_address() + 1)
+                    .saturating_sub(MEMORY_ROWS * MEMORY_ROW_BYTES)
+                    .max(segment.get_low_address()),
+            };
+
+            self.set_memory_address(address);
+        }
+    }
 }
 
 pub struct VMViewer<B: Backend> {
     terminal: Box<Terminal<B>>,
+    view: ViewState,
 }
 
 pub fn make_crossterm_viewer() -> VMViewer<CrosstermBackend<io::Stdout>> {
@@ -38,82 +248,250 @@ pub fn make_crossterm_viewer() -> VMViewer<CrosstermBackend<io::Stdout>> {
 
     VMViewer {
         terminal: Box::new(terminal),
+        view: ViewState {
+            register_tab: RegisterTab::General,
+            instruction_cursor: 0,
+            memory_address: None,
+            memory_format: ValueFormat::Hex,
+            register_formats: [ValueFormat::Hex; 3],
+            address_input: None,
+            console_input: console::LineEditor::new(),
+            console_scroll: 0,
+        },
     }
 }
 
-fn ui_registers<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+fn ui_registers<B: Backend>(root: &mut Frame<B>, state: &VMState, view: &ViewState, rect: Rect) {
     let block = Block::default();
 
     root.render_widget(block, rect);
 
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(rect);
+
+    let titles = RegisterTab::ALL
+        .iter()
+        .map(|tab| Spans::from(tab.title()))
+        .collect();
+
+    let tabs = Tabs::new(titles)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!(" Registers ({}) ", view.register_format().title()))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .select(view.register_tab.index())
+        .highlight_style(
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        );
+
+    root.render_widget(tabs, chunks[0]);
+
+    match view.register_tab {
+        RegisterTab::General => ui_general_registers(root, state, view, chunks[1]),
+        RegisterTab::Coprocessor1 => ui_fpu_registers(root, state, view, chunks[1]),
+        RegisterTab::Coprocessor0 => ui_cp0_registers(root, state, view, chunks[1]),
+    }
+}
+
+fn ui_general_registers<B: Backend>(
+    root: &mut Frame<B>,
+    state: &VMState,
+    view: &ViewState,
+    rect: Rect,
+) {
     let registers_block = Block::default()
         .borders(Borders::ALL)
-        .title(" Registers ")
-        .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
 
     // Render registers
 
-    let header = Row::new(vec!["#", "Name", "Value"])
+    let header = Row::new(vec!["#", "Name", "Value", "Previous"])
         .style(Style::default().fg(Color::Yellow))
         .bottom_margin(1);
     let mut rows = vec![];
 
-    fn make_row<'a>(reg: &'a str, name: &'a str, value: u32) -> Row<'a> {
+    let vm = state.vm;
+    let format = view.register_format();
+
+    /// Make a row for the register table, highlighting the value if the last instruction
+    /// changed it.
+    fn make_row<'a>(
+        reg: String,
+        name: &'a str,
+        value: String,
+        previous: String,
+        changed: bool,
+    ) -> Row<'a> {
+        let value = Cell::from(value);
+
         Row::new(vec![
             Cell::from(reg),
             Cell::from(name),
-            Cell::from(format!("{}", value)),
+            if changed {
+                value.style(Style::default().fg(Color::Black).bg(Color::Green))
+            } else {
+                value
+            },
+            Cell::from(previous),
         ])
     }
 
-    fn make_row_hex<'a>(reg: &'a str, name: &'a str, value: u32) -> Row<'a> {
-        Row::new(vec![
-            Cell::from(reg),
-            Cell::from(name),
-            Cell::from(format!("{:#010x}", value)),
-        ])
+    let register_row = |register: u8| {
+        make_row(
+            format!("${}", register),
+            register_name(register).unwrap(),
+            format.format_word(vm.get_register(register).unwrap()),
+            format.format_word(vm.get_previous_register(register).unwrap()),
+            vm.get_last_changes()
+                .iter()
+                .any(|change| matches!(change, Change::Register(r, _) if *r == register)),
+        )
+    };
+
+    for register in 0..32 {
+        rows.push(register_row(register));
     }
 
-    let vm = state.vm;
+    rows.push(make_row(
+        String::new(),
+        "pc",
+        format!("{:#010x}", vm.get_pc()),
+        String::new(),
+        false,
+    ));
+
+    rows.push(make_row(
+        String::new(),
+        "hi",
+        format.format_word(vm.get_hi()),
+        format.format_word(vm.get_previous_hi()),
+        vm.get_last_changes()
+            .iter()
+            .any(|change| matches!(change, Change::Hi(_))),
+    ));
+    rows.push(make_row(
+        String::new(),
+        "lo",
+        format.format_word(vm.get_lo()),
+        format.format_word(vm.get_previous_lo()),
+        vm.get_last_changes()
+            .iter()
+            .any(|change| matches!(change, Change::Lo(_))),
+    ));
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(registers_block)
+        .widths(&[
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Length(11),
+            Constraint::Length(11),
+        ]);
+
+    root.render_widget(table, rect);
+}
 
-    rows.push(make_row("$0", "zero", vm.get_register(0).unwrap()));
-    rows.push(make_row("$1", "at", vm.get_register(1).unwrap()));
-    rows.push(make_row("$2", "v0", vm.get_register(2).unwrap()));
-    rows.push(make_row("$3", "v1", vm.get_register(3).unwrap()));
-    rows.push(make_row("$4", "a0", vm.get_register(4).unwrap()));
-    rows.push(make_row("$5", "a1", vm.get_register(5).unwrap()));
-    rows.push(make_row("$6", "a2", vm.get_register(6).unwrap()));
-    rows.push(make_row("$7", "a3", vm.get_register(7).unwrap()));
-    rows.push(make_row("$8", "t0", vm.get_register(8).unwrap()));
-    rows.push(make_row("$9", "t1", vm.get_register(9).unwrap()));
-    rows.push(make_row("$10", "t2", vm.get_register(10).unwrap()));
-    rows.push(make_row("$11", "t3", vm.get_register(11).unwrap()));
-    rows.push(make_row("$12", "t4", vm.get_register(12).unwrap()));
-    rows.push(make_row("$13", "t5", vm.get_register(13).unwrap()));
-    rows.push(make_row("$14", "t6", vm.get_register(14).unwrap()));
-    rows.push(make_row("$15", "t7", vm.get_register(15).unwrap()));
-    rows.push(make_row("$16", "s0", vm.get_register(16).unwrap()));
-    rows.push(make_row("$17", "s1", vm.get_register(17).unwrap()));
-    rows.push(make_row("$18", "s2", vm.get_register(18).unwrap()));
-    rows.push(make_row("$19", "s3", vm.get_register(19).unwrap()));
-    rows.push(make_row("$20", "s4", vm.get_register(20).unwrap()));
-    rows.push(make_row("$21", "s5", vm.get_register(21).unwrap()));
-    rows.push(make_row("$22", "s6", vm.get_register(22).unwrap()));
-    rows.push(make_row("$23", "s7", vm.get_register(23).unwrap()));
-    rows.push(make_row("$24", "t8", vm.get_register(24).unwrap()));
-    rows.push(make_row("$25", "t9", vm.get_register(25).unwrap()));
-    rows.push(make_row("$26", "k0", vm.get_register(26).unwrap()));
-    rows.push(make_row("$27", "k1", vm.get_register(27).unwrap()));
-    rows.push(make_row_hex("$28", "gp", vm.get_register(28).unwrap()));
-    rows.push(make_row_hex("$29", "sp", vm.get_register(29).unwrap()));
-    rows.push(make_row_hex("$30", "fp", vm.get_register(30).unwrap()));
-    rows.push(make_row_hex("$31", "ra", vm.get_register(31).unwrap()));
-
-    rows.push(make_row_hex("", "pc", vm.get_pc() as u32));
-
-    rows.push(make_row("", "hi", vm.get_hi()));
-    rows.push(make_row("", "lo", vm.get_lo()));
+fn ui_fpu_registers<B: Backend>(
+    root: &mut Frame<B>,
+    state: &VMState,
+    view: &ViewState,
+    rect: Rect,
+) {
+    let registers_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+
+    let format = view.register_format();
+    let header = Row::new(vec!["Name", "Float", "Double", "Bits"])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1);
+    let mut rows = vec![];
+
+    let fpu = &state.vm.fpu;
+
+    for register in 0..32 {
+        // Doubles occupy an even/odd register pair, so only show them on even registers.
+        let double = if register % 2 == 0 {
+            format!("{}", fpu.get_double(register).unwrap())
+        } else {
+            String::new()
+        };
+
+        rows.push(Row::new(vec![
+            Cell::from(Register::Float(register).to_string()),
+            Cell::from(format!("{}", fpu.get_float(register).unwrap())),
+            Cell::from(double),
+            Cell::from(format.format_word(fpu.get_register(register).unwrap())),
+        ]));
+    }
+
+    let flags = (0..8)
+        .map(|flag| {
+            if fpu.get_condition_flag(flag) {
+                '1'
+            } else {
+                '0'
+            }
+        })
+        .collect::<String>();
+
+    rows.push(Row::new(vec![
+        Cell::from("flags"),
+        Cell::from(flags),
+        Cell::from(""),
+        Cell::from(""),
+    ]));
+    rows.push(Row::new(vec![
+        Cell::from("fcsr"),
+        Cell::from(""),
+        Cell::from(""),
+        Cell::from(format.format_word(fpu.get_fcsr())),
+    ]));
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(registers_block)
+        .widths(&[
+            Constraint::Percentage(15),
+            Constraint::Percentage(25),
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+        ]);
+
+    root.render_widget(table, rect);
+}
+
+fn ui_cp0_registers<B: Backend>(
+    root: &mut Frame<B>,
+    state: &VMState,
+    view: &ViewState,
+    rect: Rect,
+) {
+    let registers_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+
+    let header = Row::new(vec!["#", "Name", "Value"])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1);
+
+    let cp0 = &state.vm.cp0;
+    let format = view.register_format();
+    let rows = Coprocessor0::REGISTERS.iter().map(|(number, name)| {
+        Row::new(vec![
+            Cell::from(format!("${}", number)),
+            Cell::from(*name),
+            Cell::from(format.format_word(cp0.get_register(*number).unwrap())),
+        ])
+    });
 
     let table = Table::new(rows)
         .header(header)
@@ -127,25 +505,210 @@ fn ui_registers<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
     root.render_widget(table, rect);
 }
 
-fn ui_console<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+fn ui_memory<B: Backend>(root: &mut Frame<B>, state: &VMState, view: &ViewState, rect: Rect) {
+    let vm = state.vm;
+    let start = view.get_memory_address(vm);
+
+    let title = match &view.address_input {
+        Some(input) => format!(" Go to: {}_ ", input),
+        None => {
+            let segment = vm
+                .memory
+                .get_segment(start)
+                .map_or("unmapped", |segment| segment.name.as_str());
+
+            format!(" Memory: {} ({}) ", segment, view.memory_format.title())
+        }
+    };
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .border_type(BorderType::Rounded);
+
+    let header = Row::new(vec!["Address", "+0", "+4", "+8", "+c", "ASCII"])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1);
+
+    // Highlight the words written by the last instruction.
+    let written = Style::default().fg(Color::Black).bg(Color::Green);
+
+    let rows = (0..MEMORY_ROWS).map(|row| {
+        let address = start + row * MEMORY_ROW_BYTES;
+        let mut cells = vec![Cell::from(format!("{:#010x}", address))];
+
+        for word_address in (address..address + MEMORY_ROW_BYTES).step_by(4) {
+            let cell = match vm.memory.get_word(word_address) {
+                Ok(word) => Cell::from(view.memory_format.format_word(word)),
+                Err(_) => Cell::from("--------").style(Style::default().fg(Color::DarkGray)),
+            };
+
+            if (word_address..word_address + 4).any(|a| vm.memory.was_last_written(a)) {
+                cells.push(cell.style(written));
+            } else {
+                cells.push(cell);
+            }
+        }
+
+        let text = (address..address + MEMORY_ROW_BYTES)
+            .map(|a| vm.memory.get_byte(a).map_or(' ', ascii))
+            .collect::<String>();
+        cells.push(Cell::from(text));
+
+        Row::new(cells)
+    });
+
+    let table = Table::new(rows).header(header).block(block).widths(&[
+        Constraint::Length(10),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(11),
+        Constraint::Length(MEMORY_ROW_BYTES as u16),
+    ]);
+
+    root.render_widget(table, rect);
+}
+
+fn ui_watch_expressions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Watch ")
+        .border_type(BorderType::Rounded);
+
+    let header = Row::new(vec!["Expression", "Value"])
+        .style(Style::default().fg(Color::Yellow))
+        .bottom_margin(1);
+
+    let rows = state.watch_expressions.iter().map(|expression| {
+        let value = match state.vm.evaluate(expression, None) {
+            Ok(value) => Cell::from(format!("{} ({:#x})", value, value as u32)),
+            Err(message) => Cell::from(message).style(Style::default().fg(Color::Red)),
+        };
+
+        Row::new(vec![Cell::from(expression.source().to_owned()), value])
+    });
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(block)
+        .widths(&[Constraint::Percentage(50), Constraint::Percentage(50)]);
+
+    root.render_widget(table, rect);
+}
+
+/// Show the instruction count and cycle estimate, then the counts by category, format and
+/// mnemonic, with the most executed mnemonics first.
+fn ui_statistics<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+    let statistics = &state.vm.statistics;
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Statistics ")
+        .border_type(BorderType::Rounded);
+
+    let count_row = |name: String, count: u64| {
+        Row::new(vec![
+            Cell::from(name),
+            Cell::from(count.to_string()),
+            Cell::from(format!("{:.1}%", statistics.percentage(count))),
+        ])
+    };
+    let heading = |name: &'static str| {
+        Row::new(vec![Cell::from(name)]).style(Style::default().fg(Color::Yellow))
+    };
+
+    let mut rows = vec![
+        Row::new(vec![
+            Cell::from("Total"),
+            Cell::from(statistics.get_total().to_string()),
+        ]),
+        Row::new(vec![
+            Cell::from("Cycles"),
+            Cell::from(statistics.get_cycles().to_string()),
+            Cell::from(format!("CPI {:.2}", statistics.get_cpi())),
+        ]),
+        heading("Category"),
+    ];
+
+    for category in InstructionCategory::ALL {
+        rows.push(count_row(
+            category.name().to_owned(),
+            statistics.get_category_count(category),
+        ));
+    }
+
+    rows.push(heading("Format"));
+
+    for (name, format) in [
+        ("R", InstructionFormat::R),
+        ("I", InstructionFormat::I),
+        ("J", InstructionFormat::J),
+    ] {
+        rows.push(count_row(
+            name.to_owned(),
+            statistics.get_format_count(format),
+        ));
+    }
+
+    rows.push(heading("Instruction"));
+
+    for (name, count) in statistics.get_mnemonic_counts() {
+        rows.push(count_row(name.to_owned(), count));
+    }
+
+    let table = Table::new(rows).block(block).widths(&[
+        Constraint::Length(11),
+        Constraint::Length(12),
+        Constraint::Length(9),
+    ]);
+
+    root.render_widget(table, rect);
+}
+
+/// The prompt shown before the input line for a read syscall.
+fn input_prompt(request: InputRequest) -> &'static str {
+    match request {
+        InputRequest::Integer => "int> ",
+        InputRequest::Float => "float> ",
+        InputRequest::Double => "double> ",
+        InputRequest::String { .. } => "string> ",
+        InputRequest::Character => "char> ",
+    }
+}
+
+fn ui_console<B: Backend>(root: &mut Frame<B>, state: &VMState, view: &ViewState, rect: Rect) {
     let layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(5), Constraint::Percentage(90)].as_ref())
+        .constraints([Constraint::Length(9), Constraint::Percentage(90)].as_ref())
         .split(rect);
 
-    let mut color = if state.paused { Color::Yellow } else { Color::White };
+    let mut color = if state.paused {
+        Color::Yellow
+    } else {
+        Color::White
+    };
 
     if state.halted {
         color = Color::Red;
     }
 
-    let mut title = " Running ";
+    let mut title = format!(" Running ({}) ", state.speed.title());
 
     if state.paused {
-        title = " Paused ";
+        title = format!(" Paused ({}) ", state.speed.title());
     }
     if state.halted {
-        title = " Halted ";
+        title = String::from(" Halted ");
+    }
+
+    // The program only waits for input while it is running, so this is not a pause.
+    let input_request = state.vm.get_input_request().filter(|_| !state.halted);
+
+    if input_request.is_some() {
+        color = Color::Cyan;
+        title = String::from(" Waiting for input ");
     }
 
     let control_block = Block::default()
@@ -154,31 +717,129 @@ fn ui_console<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
         .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
 
-    let control_text = Paragraph::new(if state.paused {
-        "[P] Resume\n[R] Reset\n[Q] Quit"
+    let run_control = if state.halted {
+        ""
+    } else if input_request.is_some() {
+        "[Enter] Submit input  "
+    } else if state.paused {
+        "[P] Resume  "
     } else {
-        if state.halted {
-            "[R] Reset\n[Q] Quit"
-        } else {
-            "[P] Pause\n[R] Reset\n[Q] Quit"
-        }
-    })
+        "[P] Pause  "
+    };
+
+    let control_text = Paragraph::new(format!(
+        "{}[R] Reset  [Q] Quit\n[S] Step  [N] Step over  [O] Step out\n\
+         [U] Step back  [Shift+U] Back to breakpoint\n[Tab] Registers  [V] Register format\n\
+         [Up/Down] Select  [B] Breakpoint  [+/-] Run speed\n\
+         [M] Segment  [PgUp/PgDn] Page  [F] Format  [G] Go to\n\
+         [[/]] Scroll console  [End] Follow output  [W] Save console",
+        run_control
+    ))
     .style(Style::default().fg(color))
     .block(control_block)
     .alignment(Alignment::Left);
 
+    let console_title = if view.console_scroll > 0 {
+        format!(" Console (scrolled up {}) ", view.console_scroll)
+    } else {
+        String::from(" Console ")
+    };
+
     let console_block = Block::default()
         .borders(Borders::ALL)
-        .title(" Console ")
+        .title(console_title)
         .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
 
-    root.render_widget(state.console.as_paragraph().block(console_block), layout[1]);
+    let input = input_request.map(|request| view.console_input.as_spans(input_prompt(request)));
+
+    let height = layout[1].height.saturating_sub(2) as usize;
+
+    root.render_widget(
+        state
+            .console
+            .as_paragraph(input, height, view.console_scroll)
+            .block(console_block),
+        layout[1],
+    );
 
     root.render_widget(control_text, layout[0]);
 }
 
-fn ui_next_instructions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+/// Show the source lines around the one the PC is on, for programs with source information.
+fn ui_source<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+    let vm = state.vm;
+    let debug_info = &vm.debug_info;
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(" Source ")
+        .title_alignment(Alignment::Center)
+        .border_type(BorderType::Rounded);
+
+    let current = debug_info.line_of(vm.get_pc());
+
+    // Keep the current line in the middle of the panel where possible.
+    let height = rect.height.saturating_sub(2) as usize;
+    let last = debug_info.get_source().len();
+    let first = current
+        .unwrap_or(1)
+        .saturating_sub(height / 2)
+        .min(last.saturating_sub(height) + 1)
+        .max(1);
+
+    let rows = (first..=last).take(height).map(|line| {
+        let breakpoint = debug_info
+            .address_of(line)
+            .is_some_and(|address| vm.has_breakpoint_at(address));
+
+        let row = Row::new(vec![
+            Cell::from(if breakpoint { "●" } else { "" }).style(Style::default().fg(Color::Red)),
+            Cell::from(format!("{:>4}", line)).style(Style::default().fg(Color::DarkGray)),
+            Cell::from(
+                debug_info
+                    .source_line(line)
+                    .unwrap_or("")
+                    .replace('\t', "    "),
+            ),
+        ]);
+
+        if Some(line) == current {
+            row.style(Style::default().fg(Color::Black).bg(Color::Yellow))
+        } else {
+            row
+        }
+    });
+
+    let table = Table::new(rows).block(block).widths(&[
+        Constraint::Length(2),
+        Constraint::Length(4),
+        Constraint::Percentage(100),
+    ]);
+
+    root.render_widget(table, rect);
+}
+
+/// The address the instruction at the PC branches or jumps to, if it has a fixed target.
+fn jump_target(vm: &VM) -> Option<usize> {
+    let code = vm.memory.get_word(vm.get_pc()).ok()?;
+    let instruction = vm.decode_instruction(code).ok()?;
+
+    if instruction.is_null() {
+        return None;
+    }
+
+    disassemble(&instruction, vm.get_pc() as u32, |_| None)
+        .target
+        .map(|target| target as usize)
+}
+
+fn ui_next_instructions<B: Backend>(
+    root: &mut Frame<B>,
+    state: &VMState,
+    view: &ViewState,
+    rect: Rect,
+) {
     let block = Block::default();
 
     root.render_widget(block, rect);
@@ -188,16 +849,31 @@ fn ui_next_instructions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect:
         .title_alignment(Alignment::Center)
         .border_type(BorderType::Rounded);
 
-    let header = Row::new(vec!["Address", "Code", "Decoded Instruction"])
+    let header = Row::new(vec!["", "Address", "Code", "Decoded Instruction"])
         .style(Style::default().fg(Color::Yellow))
         .bottom_margin(1);
 
     let mut rows = vec![];
 
-    /// Make a row for the memory table.
-    fn make_row<'a>(address: u32, code: u32, instruction: Spans<'a>) -> Row<'a> {
+    /// Make a row for the memory table. The address of a jump target is highlighted.
+    fn make_row<'a>(
+        breakpoint: bool,
+        target: bool,
+        address: u32,
+        code: u32,
+        instruction: Spans<'a>,
+    ) -> Row<'a> {
+        let address_style = if target {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+
         Row::new(vec![
-            Cell::from(format!("{:#010x}", address)),
+            Cell::from(if breakpoint { "●" } else { "" }).style(Style::default().fg(Color::Red)),
+            Cell::from(format!("{:#010x}", address)).style(address_style),
             Cell::from(format!("{:08x}", code)),
             Cell::from(instruction),
         ])
@@ -205,42 +881,38 @@ fn ui_next_instructions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect:
 
     let mut decoded_instructions: Vec<Spans> = vec![];
     let vm = state.vm;
-    
-    for i in vm.get_pc() / 4..vm.get_pc() / 4 + 10 {
+    let target = jump_target(vm);
+
+    for i in vm.get_pc() / 4..vm.get_pc() / 4 + INSTRUCTION_ROWS {
         let address = i * 4;
         let code = vm.memory.get_word(address).unwrap();
         let instruction = vm.decode_instruction(code);
 
         let mut spans = vec![];
+
+        for label in vm.debug_info.symbols_at(address) {
+            spans.push(Span::styled(
+                format!("{}: ", label),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+
         if let Ok(instruction) = instruction {
             if !instruction.is_null() {
+                let disassembly = disassemble(&instruction, address as u32, |target| {
+                    vm.debug_info.symbols_at(target as usize).next()
+                });
+
                 spans.push(Span::styled(
-                    format!("{}", instruction.base.name),
+                    disassembly.mnemonic,
                     Style::default()
                         .fg(Color::Green)
                         .add_modifier(Modifier::BOLD),
                 ));
-
-                match instruction.args {
-                    InstructionArgs::RFormat(args) => {
-                        spans.push(Span::styled(
-                            format!(" ${}, ${}, ${}", args.rd, args.rs, args.rt),
-                            Style::default().fg(Color::Green),
-                        ));
-                    }
-                    InstructionArgs::IFormat(args) => {
-                        spans.push(Span::styled(
-                            format!(" ${}, ${}, {:#06x}", args.rt, args.rs, args.imm),
-                            Style::default().fg(Color::Green),
-                        ));
-                    }
-                    InstructionArgs::JFormat(args) => {
-                        spans.push(Span::styled(
-                            format!(" {:#010x}", args.address << 2),
-                            Style::default().fg(Color::Green),
-                        ));
-                    }
-                }
+                spans.push(Span::styled(
+                    format!(" {}", disassembly.operands),
+                    Style::default().fg(Color::Green),
+                ));
             }
         } else {
             spans.push(Span::styled("???", Style::default().fg(Color::Red)));
@@ -248,17 +920,26 @@ fn ui_next_instructions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect:
 
         decoded_instructions.push(Spans::from(spans));
 
-        rows.push(make_row(
+        let mut row = make_row(
+            vm.has_breakpoint_at(address),
+            target == Some(address),
             address as u32,
             code,
             decoded_instructions.last().unwrap().to_owned(),
-        ));
+        );
+
+        if address == vm.get_pc() + view.instruction_cursor * 4 {
+            row = row.style(Style::default().bg(Color::DarkGray));
+        }
+
+        rows.push(row);
     }
 
     let table = Table::new(rows)
         .header(header)
         .block(memory_block)
         .widths(&[
+            Constraint::Length(2),
             Constraint::Percentage(20),
             Constraint::Percentage(20),
             Constraint::Percentage(60),
@@ -267,28 +948,36 @@ fn ui_next_instructions<B: Backend>(root: &mut Frame<B>, state: &VMState, rect:
     root.render_widget(table, rect);
 }
 
-fn ui_state<B: Backend>(root: &mut Frame<B>, state: &VMState, rect: Rect) {
+fn ui_state<B: Backend>(root: &mut Frame<B>, state: &VMState, view: &ViewState, rect: Rect) {
     let block = Block::default();
 
     root.render_widget(block, rect);
 
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)].as_ref())
         .split(rect);
 
-    ui_registers(root, state, chunks[0]);
+    ui_registers(root, state, view, chunks[0]);
 
     let v_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(INSTRUCTION_ROWS as u16 + 4),
+                Constraint::Length(MEMORY_ROWS as u16 + 4),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
         .split(chunks[1]);
 
-    ui_next_instructions(root, state, v_chunks[0]);
-    ui_console(root, state, v_chunks[1]);
+    ui_next_instructions(root, state, view, v_chunks[0]);
+    ui_memory(root, state, view, v_chunks[1]);
+    ui_console(root, state, view, v_chunks[2]);
 }
 
-fn ui<B: Backend>(root: &mut Frame<B>, vm: &VMState) {
+fn ui<B: Backend>(root: &mut Frame<B>, vm: &VMState, view: &ViewState) {
     let size = root.size();
 
     let block = Block::default()
@@ -307,18 +996,37 @@ fn ui<B: Backend>(root: &mut Frame<B>, vm: &VMState) {
 
     // render state
 
-    ui_state(root, vm, chunks[0]);
+    ui_state(root, vm, view, chunks[0]);
 
-    // render controls
+    // Programs assembled from source show it alongside the disassembly.
+    if vm.vm.debug_info.get_source().is_empty() {
+        let side_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(chunks[1]);
 
-    let controls_block = Block::default()
-        .borders(Borders::ALL)
-        .title(" Controls ")
-        .title_alignment(Alignment::Center)
-        .border_type(BorderType::Rounded);
+        ui_watch_expressions(root, vm, side_chunks[0]);
+        ui_statistics(root, vm, side_chunks[1]);
+    } else {
+        let side_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Percentage(50),
+                    Constraint::Percentage(20),
+                    Constraint::Percentage(30),
+                ]
+                .as_ref(),
+            )
+            .split(chunks[1]);
+
+        ui_source(root, vm, side_chunks[0]);
+        ui_watch_expressions(root, vm, side_chunks[1]);
+        ui_statistics(root, vm, side_chunks[2]);
+    }
 }
 
-impl<'a, B: Backend> VMViewer<B> {
+impl<B: Backend> VMViewer<B> {
     pub fn init(&mut self) -> Result<(), io::Error> {
         terminal::enable_raw_mode()?;
 
@@ -335,17 +1043,19 @@ impl<'a, B: Backend> VMViewer<B> {
         Ok(())
     }
 
-    /// Update the UI with the current state of the VM.
+    /// Update the UI with the current state of the VM, then wait up to `timeout` for a key.
     pub fn update(
         &mut self,
         state: &VMState,
+        timeout: Duration,
     ) -> Result<VMViewerEvent, io::Error> {
+        let view = &self.view;
         self.terminal.draw(|f| {
-            ui(f, state);
+            ui(f, state, view);
         })?;
 
         // handle input
-        let poll = event::poll(std::time::Duration::from_millis(100))?;
+        let poll = event::poll(timeout)?;
 
         if !poll {
             return Ok(VMViewerEvent::None);
@@ -353,21 +1063,229 @@ impl<'a, B: Backend> VMViewer<B> {
 
         let event = event::read();
 
-        match event {
-            Ok(event::Event::Key(key)) => match key.code {
+        if let Ok(event::Event::Key(key)) = event {
+            // While an address is being typed, keys edit it instead of controlling the VM.
+            if let Some(input) = &mut self.view.address_input {
+                match key.code {
+                    event::KeyCode::Char(c) if c.is_ascii_hexdigit() || c == 'x' => input.push(c),
+                    event::KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    event::KeyCode::Enter => {
+                        let input = input.trim_start_matches("0x");
+
+                        if let Ok(address) = usize::from_str_radix(input, 16) {
+                            self.view.set_memory_address(address);
+                        }
+
+                        self.view.address_input = None;
+                    }
+                    event::KeyCode::Esc => self.view.address_input = None,
+                    _ => {}
+                }
+
+                return Ok(VMViewerEvent::None);
+            }
+
+            // While the program waits for input, keys edit the input line. Ctrl+C still quits.
+            if let Some(request) = state.vm.get_input_request().filter(|_| !state.halted) {
+                if key.code == event::KeyCode::Char('c')
+                    && key.modifiers.contains(event::KeyModifiers::CONTROL)
+                {
+                    return Ok(VMViewerEvent::Quit);
+                }
+
+                return Ok(edit_input(&mut self.view.console_input, request, key.code));
+            }
+
+            match key.code {
                 // check for "q" or "ctrl+c"
                 event::KeyCode::Char('q') => return Ok(VMViewerEvent::Quit),
-                event::KeyCode::Char('c') => {
-                    if key.modifiers.contains(event::KeyModifiers::CONTROL) {
-                        return Ok(VMViewerEvent::Quit);
-                    }
+                event::KeyCode::Char('c')
+                    if key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                {
+                    return Ok(VMViewerEvent::Quit)
                 }
                 event::KeyCode::Char('p') => return Ok(VMViewerEvent::TogglePause),
+                event::KeyCode::Char('r') => return Ok(VMViewerEvent::Reset),
+                event::KeyCode::Char('s') => return Ok(VMViewerEvent::Step),
+                event::KeyCode::Char('n') => return Ok(VMViewerEvent::StepOver),
+                event::KeyCode::Char('o') => return Ok(VMViewerEvent::StepOut),
+                event::KeyCode::Char('u') => return Ok(VMViewerEvent::StepBack),
+                event::KeyCode::Char('U') => return Ok(VMViewerEvent::RunBackward),
+                event::KeyCode::Tab => self.view.register_tab = self.view.register_tab.next(),
+                event::KeyCode::Char('v') => self.view.next_register_format(),
+                event::KeyCode::Up => {
+                    self.view.instruction_cursor = self.view.instruction_cursor.saturating_sub(1)
+                }
+                event::KeyCode::Down => {
+                    self.view.instruction_cursor =
+                        (self.view.instruction_cursor + 1).min(INSTRUCTION_ROWS - 1)
+                }
+                event::KeyCode::Char('m') => self.view.next_segment(state.vm),
+                event::KeyCode::Char('f') => {
+                    self.view.memory_format = self.view.memory_format.next_of(&ValueFormat::MEMORY)
+                }
+                event::KeyCode::Char('g') => self.view.address_input = Some(String::new()),
+                event::KeyCode::PageUp => {
+                    let address = self.view.get_memory_address(state.vm);
+                    self.view
+                        .set_memory_address(address.saturating_sub(MEMORY_ROWS * MEMORY_ROW_BYTES));
+                }
+                event::KeyCode::PageDown => {
+                    let address = self.view.get_memory_address(state.vm);
+                    self.view
+                        .set_memory_address(address.saturating_add(MEMORY_ROWS * MEMORY_ROW_BYTES));
+                }
+                event::KeyCode::Char('[') => {
+                    self.view.console_scroll = (self.view.console_scroll + CONSOLE_SCROLL_LINES)
+                        .min(state.console.len().saturating_sub(1))
+                }
+                event::KeyCode::Char(']') => {
+                    self.view.console_scroll = self
+                        .view
+                        .console_scroll
+                        .saturating_sub(CONSOLE_SCROLL_LINES)
+                }
+                event::KeyCode::End => self.view.console_scroll = 0,
+                event::KeyCode::Char('w') => return Ok(VMViewerEvent::SaveConsole),
+                event::KeyCode::Char('+') | event::KeyCode::Char('=') => {
+                    return Ok(VMViewerEvent::Faster)
+                }
+                event::KeyCode::Char('-') => return Ok(VMViewerEvent::Slower),
+                event::KeyCode::Char('b') => {
+                    let address = state.vm.get_pc() + self.view.instruction_cursor * 4;
+                    return Ok(VMViewerEvent::ToggleBreakpoint(
+                        BreakpointLocation::Address(address),
+                    ));
+                }
                 _ => {}
-            },
-            _ => {}
+            }
         }
 
         Ok(VMViewerEvent::None)
     }
 }
+
+/// Handle a key pressed while the program is waiting for input. Read character submits
+/// as soon as a key is pressed, and the other read syscalls submit a line on Enter.
+fn edit_input(
+    input: &mut console::LineEditor,
+    request: InputRequest,
+    key: event::KeyCode,
+) -> VMViewerEvent {
+    match (request, key) {
+        (InputRequest::Character, event::KeyCode::Char(c)) => {
+            return VMViewerEvent::SubmitInput(c.to_string())
+        }
+        (InputRequest::Character, event::KeyCode::Enter) => {
+            return VMViewerEvent::SubmitInput(String::from("\n"))
+        }
+        (_, event::KeyCode::Enter) => {
+            return VMViewerEvent::SubmitInput(input.take() + "\n");
+        }
+        // Read string keeps at most max_length - 1 characters, so don't accept more.
+        (InputRequest::String { max_length }, event::KeyCode::Char(_))
+            if input.len() + 1 >= max_length as usize => {}
+        (_, event::KeyCode::Char(c)) => input.insert(c),
+        (_, event::KeyCode::Backspace) => input.backspace(),
+        (_, event::KeyCode::Delete) => input.delete(),
+        (_, event::KeyCode::Left) => input.left(),
+        (_, event::KeyCode::Right) => input.right(),
+        (_, event::KeyCode::Home) => input.home(),
+        (_, event::KeyCode::End) => input.end(),
+        _ => {}
+    }
+
+    VMViewerEvent::None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::loader::vm_running;
+    use event::KeyCode;
+
+    /// Press `keys` while the program waits for `request`, and return the input submitted, if
+    /// any.
+    fn press(
+        input: &mut console::LineEditor,
+        request: InputRequest,
+        keys: &[KeyCode],
+    ) -> Option<String> {
+        keys.iter()
+            .find_map(|key| match edit_input(input, request, *key) {
+                VMViewerEvent::SubmitInput(text) => Some(text),
+                _ => None,
+            })
+    }
+
+    fn chars(text: &str) -> Vec<KeyCode> {
+        text.chars().map(KeyCode::Char).collect()
+    }
+
+    #[test]
+    fn read_character_submits_each_key() {
+        let mut input = console::LineEditor::new();
+
+        assert_eq!(
+            press(&mut input, InputRequest::Character, &chars("x")).as_deref(),
+            Some("x")
+        );
+        assert_eq!(
+            press(&mut input, InputRequest::Character, &[KeyCode::Enter]).as_deref(),
+            Some("\n")
+        );
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn lines_are_edited_and_submitted_on_enter() {
+        let mut input = console::LineEditor::new();
+        let mut keys = chars("12");
+        keys.extend([
+            KeyCode::Left,
+            KeyCode::Char('3'),
+            KeyCode::End,
+            KeyCode::Backspace,
+        ]);
+
+        assert_eq!(press(&mut input, InputRequest::Integer, &keys), None);
+        assert_eq!(
+            press(
+                &mut input,
+                InputRequest::Integer,
+                &[KeyCode::Home, KeyCode::Char('-'), KeyCode::Enter]
+            )
+            .as_deref(),
+            Some("-13\n")
+        );
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn read_string_stops_accepting_at_its_maximum_length() {
+        let mut input = console::LineEditor::new();
+        let request = InputRequest::String { max_length: 4 };
+
+        assert_eq!(press(&mut input, request, &chars("abcde")), None);
+        assert_eq!(input.len(), 3);
+        assert_eq!(
+            press(&mut input, request, &[KeyCode::Enter]).as_deref(),
+            Some("abc\n")
+        );
+    }
+
+    #[test]
+    fn jump_target_is_that_of_the_next_instruction() {
+        // j 0x00400010; beq $zero, $zero, -2; addiu $t0, $zero, 1
+        let mut vm = vm_running(&[0x08100004, 0x1000fffe, 0x24080001]);
+        assert_eq!(jump_target(&vm), Some(0x00400010));
+
+        vm.set_pc(0x00400004);
+        assert_eq!(jump_target(&vm), Some(0x00400000));
+
+        vm.set_pc(0x00400008);
+        assert_eq!(jump_target(&vm), None);
+    }
+}
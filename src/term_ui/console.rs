@@ -1,3 +1,7 @@
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+
 use tui::{
     style::{Color, Modifier, Style},
     text::{Span, Spans},
@@ -6,15 +10,45 @@ use tui::{
 
 use crate::runtime::errors::{FatalErrorType, RuntimeError};
 
+/// The number of lines kept in the console. Older lines are dropped.
+const SCROLLBACK_LINES: usize = 1000;
+
 pub struct Console<'a> {
-    lines: Vec<Spans<'a>>,
+    lines: VecDeque<Spans<'a>>,
+    /// Whether the last line is program output that has not ended with a line break yet,
+    /// so that more output continues it.
+    line_open: bool,
 }
 
 impl<'a> Console<'a> {
     pub fn new() -> Console<'a> {
         Console {
             // Add an empty line to start with, just for visual purposes.
-            lines: vec![Spans::from(vec![Span::raw("")])], 
+            lines: VecDeque::from(vec![Spans::from(vec![Span::raw("")])]),
+            line_open: false,
+        }
+    }
+
+    /// Append text printed by the program. Output without a trailing line break is
+    /// continued by the next output or input echo.
+    pub fn output(&mut self, text: &str) {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                // A line break ends the current line, which may be an empty one.
+                if !self.line_open {
+                    self.add_line(Spans::default());
+                }
+                self.line_open = false;
+            }
+
+            if !part.is_empty() {
+                if !self.line_open {
+                    self.add_line(Spans::default());
+                    self.line_open = true;
+                }
+
+                self.push_to_last_line(Span::raw(part.to_owned()));
+            }
         }
     }
 
@@ -29,6 +63,7 @@ impl<'a> Console<'a> {
                     FatalErrorType::IllegalMemoryAccess => "ILLEGAL_MEMORY_ACCESS",
                     FatalErrorType::IllegalInstruction => "ILLEGAL_INSTRUCTION",
                     FatalErrorType::IllegalRegisterAccess => "ILLEGAL_REGISTER",
+                    FatalErrorType::InvalidSyscall => "INVALID_SYSCALL",
                 },
                 Style::default().fg(Color::Red),
             ),
@@ -46,6 +81,30 @@ impl<'a> Console<'a> {
         ]));
     }
 
+    pub fn warning(&mut self, message: &str) {
+        self.add_line(Spans::from(vec![
+            Span::styled(
+                "[warning] ",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(message.to_owned()),
+        ]));
+    }
+
+    pub fn paused(&mut self, message: &str) {
+        self.add_line(Spans::from(vec![
+            Span::styled(
+                "[paused] ",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(message.to_owned()),
+        ]));
+    }
+
     pub fn execution_finished(&mut self, message: &str) {
         self.add_line(Spans::from(vec![
             Span::styled(
@@ -58,20 +117,337 @@ impl<'a> Console<'a> {
         ]));
     }
 
+    /// Echo a line of input entered for a read syscall.
+    /// The echo follows the prompt, if the program printed one on the same line.
+    pub fn input(&mut self, text: &str) {
+        let echo = Span::styled(text.to_owned(), Style::default().fg(Color::Cyan));
+
+        if self.line_open {
+            self.push_to_last_line(echo);
+            self.line_open = false;
+        } else {
+            self.add_line(Spans::from(vec![echo]));
+        }
+    }
+
+    pub fn info(&mut self, message: &str) {
+        self.add_line(Spans::from(vec![
+            Span::styled(
+                "[info] ",
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(message.to_owned()),
+        ]));
+    }
+
     fn add_line(&mut self, line: Spans<'a>) {
-        self.lines.push(line);
+        self.lines.push_back(line);
+        self.line_open = false;
+
+        if self.lines.len() > SCROLLBACK_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    fn push_to_last_line(&mut self, span: Span<'a>) {
+        if let Some(line) = self.lines.back_mut() {
+            line.0.push(span);
+        }
     }
 
     pub fn reset(&mut self) {
         self.lines.clear();
+        self.line_open = false;
+    }
+
+    /// The number of lines in the console.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Write the text of the console to a file, without styling.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text: String = self
+            .lines
+            .iter()
+            .map(|line| {
+                line.0
+                    .iter()
+                    .map(|span| span.content.as_ref())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect();
+
+        std::fs::write(path, text)
+    }
+
+    /// Render the last `height` lines of the console, or earlier ones when scrolled up by
+    /// `scroll` lines. The line being entered, if the program is waiting for input, follows
+    /// the output.
+    pub fn as_paragraph(
+        &self,
+        input: Option<Spans<'a>>,
+        height: usize,
+        scroll: usize,
+    ) -> Paragraph<'a> {
+        let mut lines: Vec<Spans<'a>> = self.lines.iter().cloned().collect();
+
+        if let Some(input) = input {
+            match lines.last_mut() {
+                Some(line) if self.line_open => line.0.extend(input.0),
+                _ => lines.push(input),
+            }
+        }
+
+        let top = lines.len().saturating_sub(height + scroll);
+
+        Paragraph::new(lines).scroll((top as u16, 0))
     }
+}
+
+/// A single line of text being edited, with a cursor, used to enter input for read syscalls.
+#[derive(Default)]
+pub struct LineEditor {
+    text: Vec<char>,
+    /// The index in `text` that the next character is inserted at.
+    cursor: usize,
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Delete the character before the cursor.
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+    }
+
+    /// Delete the character under the cursor.
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.text.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Take the text entered so far, leaving the editor empty.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text).into_iter().collect()
+    }
+
+    /// Show the text after a prompt, with the cursor highlighted.
+    pub fn as_spans(&self, prompt: &str) -> Spans<'static> {
+        let before: String = self.text[..self.cursor].iter().collect();
+        let under = self.text.get(self.cursor).copied().unwrap_or(' ');
+        let after: String = self.text[(self.cursor + 1).min(self.text.len())..]
+            .iter()
+            .collect();
+
+        Spans::from(vec![
+            Span::styled(
+                prompt.to_owned(),
+                Style::default()
+                    .fg(Color::Cyan)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(before),
+            Span::styled(
+                under.to_string(),
+                Style::default().add_modifier(Modifier::REVERSED),
+            ),
+            Span::raw(after),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tui::backend::TestBackend;
+    use tui::Terminal;
+
+    use super::*;
+
+    fn text(console: &Console) -> Vec<String> {
+        console
+            .lines
+            .iter()
+            .map(|line| line.0.iter().map(|span| span.content.as_ref()).collect())
+            .collect()
+    }
+
+    /// Render the console in a terminal of the given height, and return the visible rows.
+    fn rendered(console: &Console, height: u16, scroll: usize) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(8, height)).unwrap();
+        terminal
+            .draw(|f| {
+                f.render_widget(
+                    console.as_paragraph(None, height as usize, scroll),
+                    f.size(),
+                )
+            })
+            .unwrap();
+
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.symbol.as_str())
+                    .collect::<String>()
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn output_continues_a_partial_line() {
+        let mut console = Console::new();
+        console.output("Enter");
+        console.output(" a number: ");
+        console.output("done\n\nnext");
+
+        assert_eq!(text(&console), ["", "Enter a number: done", "", "next"]);
+    }
+
+    #[test]
+    fn input_is_echoed_after_the_prompt() {
+        let mut console = Console::new();
+        console.output("Name? ");
+        console.input("Ada");
+        console.output("Hi\n");
+        console.input("again");
+
+        assert_eq!(text(&console), ["", "Name? Ada", "Hi", "again"]);
+    }
+
+    #[test]
+    fn old_lines_are_dropped_past_the_scrollback_limit() {
+        let mut console = Console::new();
+
+        for i in 0..SCROLLBACK_LINES + 5 {
+            console.output(&format!("{}\n", i));
+        }
+
+        assert_eq!(console.len(), SCROLLBACK_LINES);
+        assert_eq!(text(&console)[0], "5");
+        assert_eq!(
+            text(&console).last().unwrap(),
+            &(SCROLLBACK_LINES + 4).to_string()
+        );
+    }
+
+    #[test]
+    fn paragraph_shows_the_last_lines_unless_scrolled() {
+        let mut console = Console::new();
+        console.reset();
+        console.output("1\n2\n3\n4\n5");
+
+        assert_eq!(rendered(&console, 2, 0), ["4", "5"]);
+        assert_eq!(rendered(&console, 2, 2), ["2", "3"]);
+        // Scrolling past the first line stops there.
+        assert_eq!(rendered(&console, 2, 10), ["1", "2"]);
+    }
+
+    fn typed(text: &str) -> LineEditor {
+        let mut editor = LineEditor::new();
+        text.chars().for_each(|c| editor.insert(c));
+        editor
+    }
+
+    fn contents(spans: &Spans) -> Vec<String> {
+        spans
+            .0
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn line_editor_inserts_and_deletes_at_the_cursor() {
+        let mut editor = typed("abc");
+        editor.left();
+        editor.insert('X');
+        assert_eq!(editor.text.iter().collect::<String>(), "abXc");
+        assert_eq!(editor.cursor, 3);
+
+        editor.home();
+        editor.delete();
+        editor.end();
+        editor.backspace();
+        assert_eq!(editor.text.iter().collect::<String>(), "bX");
+
+        // Moving and deleting past either end does nothing.
+        editor.right();
+        editor.delete();
+        assert_eq!(editor.cursor, 2);
+        editor.home();
+        editor.left();
+        editor.backspace();
+        assert_eq!(editor.cursor, 0);
+        assert_eq!(editor.len(), 2);
+    }
+
+    #[test]
+    fn line_editor_take_leaves_it_empty() {
+        let mut editor = typed("42");
+        editor.left();
+
+        assert_eq!(editor.take(), "42");
+        assert!(editor.is_empty());
+        assert_eq!(editor.cursor, 0);
+
+        editor.insert('7');
+        assert_eq!(editor.take(), "7");
+    }
+
+    #[test]
+    fn line_editor_highlights_the_character_under_the_cursor() {
+        let mut editor = typed("ab");
+        assert_eq!(contents(&editor.as_spans("> ")), ["> ", "ab", " ", ""]);
 
-    pub fn as_paragraph(&self) -> Paragraph<'a> {
-        Paragraph::new(
-            self.lines
-                .iter()
-                .map(|line| line.clone())
-                .collect::<Vec<Spans>>(),
-        )
+        editor.home();
+        assert_eq!(contents(&editor.as_spans("> ")), ["> ", "", "a", "b"]);
     }
 }
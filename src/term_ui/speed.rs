@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+/// How many instructions a run executes per second, as set with the run speed slider in
+/// MARS. Steps go up from one instruction per second to unlimited.
+#[derive(Clone, Copy, PartialEq)]
+pub struct RunSpeed {
+    /// An index into `RunSpeed::STEPS`.
+    step: usize,
+}
+
+impl RunSpeed {
+    /// The available speeds in instructions per second, where None is unlimited.
+    const STEPS: [Option<u32>; 10] = [
+        Some(1),
+        Some(2),
+        Some(5),
+        Some(10),
+        Some(30),
+        Some(100),
+        Some(1_000),
+        Some(10_000),
+        Some(100_000),
+        None,
+    ];
+
+    pub fn unlimited() -> RunSpeed {
+        RunSpeed {
+            step: RunSpeed::STEPS.len() - 1,
+        }
+    }
+
+    /// The number of instructions to execute per second, or None to run as fast as possible.
+    pub fn instructions_per_second(&self) -> Option<u32> {
+        RunSpeed::STEPS[self.step]
+    }
+
+    pub fn faster(&self) -> RunSpeed {
+        RunSpeed {
+            step: (self.step + 1).min(RunSpeed::STEPS.len() - 1),
+        }
+    }
+
+    pub fn slower(&self) -> RunSpeed {
+        RunSpeed {
+            step: self.step.saturating_sub(1),
+        }
+    }
+
+    pub(super) fn title(&self) -> String {
+        match self.instructions_per_second() {
+            Some(rate) => format!("{} instr/sec", rate),
+            None => String::from("unlimited"),
+        }
+    }
+}
+
+/// Paces a run at a limited speed. Time passing gives the run credit for instructions, which
+/// carries fractions of an instruction from one frame to the next at low speeds.
+pub struct Pacer {
+    /// The time between frames.
+    frame_interval: Duration,
+    /// Instructions the run is allowed to execute but has not yet.
+    credit: f64,
+}
+
+impl Pacer {
+    /// A pacer with credit for one instruction, so that a run's first instruction executes at
+    /// once.
+    pub fn new(frame_interval: Duration) -> Pacer {
+        Pacer {
+            frame_interval,
+            credit: 1.0,
+        }
+    }
+
+    /// The number of instructions to execute in a frame at `rate` instructions per second,
+    /// `elapsed` after the previous frame.
+    pub fn steps(&mut self, rate: u32, elapsed: Duration) -> usize {
+        let rate = rate as f64;
+
+        // Don't let credit pile up when frames are slow, or the run would speed up in bursts.
+        // The limit leaves room for the fraction carried over at low speeds.
+        let limit = rate * self.frame_interval.as_secs_f64() * 2.0 + 1.0;
+        self.credit = (self.credit + rate * elapsed.as_secs_f64()).min(limit);
+
+        let steps = self.credit as usize;
+        self.credit -= steps as f64;
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+    #[test]
+    fn speeds_stop_at_either_end() {
+        let slowest = (0..20).fold(RunSpeed::unlimited(), |speed, _| speed.slower());
+
+        assert_eq!(slowest.instructions_per_second(), Some(1));
+        assert_eq!(slowest.slower().instructions_per_second(), Some(1));
+        assert_eq!(
+            RunSpeed::unlimited().faster().instructions_per_second(),
+            None
+        );
+    }
+
+    #[test]
+    fn slow_speeds_carry_fractions_across_frames() {
+        let mut pacer = Pacer::new(FRAME_INTERVAL);
+
+        // The first instruction runs at once.
+        assert_eq!(pacer.steps(10, Duration::ZERO), 1);
+
+        // Three eighths of an instruction per frame: the fraction left after each step
+        // carries over into the next.
+        let steps: Vec<usize> = (0..64)
+            .map(|_| pacer.steps(8, Duration::from_micros(46_875)))
+            .collect();
+
+        assert_eq!(steps[..6], [0, 0, 1, 0, 0, 1]);
+        assert_eq!(steps.iter().sum::<usize>(), 24);
+    }
+
+    #[test]
+    fn slow_frames_do_not_cause_bursts() {
+        let mut pacer = Pacer::new(FRAME_INTERVAL);
+
+        // After a five second stall, at most two frames' worth runs at once, plus the credit
+        // the pacer started with.
+        assert_eq!(pacer.steps(1000, Duration::from_secs(5)), 67);
+        assert_eq!(pacer.steps(1000, FRAME_INTERVAL), 33);
+    }
+}